@@ -3,26 +3,59 @@ _rustpack() {
     local cur prev words cword
     _init_completion || return
 
-    local ops="-S -Q -R -U --why doctor history"
-    local global_opts="--help -h --test --dry-run --noconfirm --needed --nodeps --noscriptlet --overwrite --asdeps --asexplicit --root --dbpath --cachedir --strict --insecure-skip-signatures --compact --verbose --json --aur --paru --"
-    local s_opts="-Sy -Su -Syu -Ss -Si -Sc -Scc -Sd -Sdd"
-    local q_opts="-Qi -Qs -Ql -Qm -Qo -Qe -Qr"
-    local r_opts="-Rs -Rn -Rd -Rdd"
+    local ops="-S -Q -R -U -D -Dk -F -Fy --why why doctor history repos rankmirrors --list-repos export import verify --clean-orphans --config-test"
+    local global_opts="--help -h --test --dry-run --explain --print --time --downgrade --noconfirm --yes-to --needed --nodeps --noscriptlet --dbonly --disable-download-timeout --overwrite --ignore --assume-installed --parallel --retries --asdeps --asexplicit --sysroot --config --root --dbpath --cachedir --logfile --gpgdir --arch --strict --insecure-skip-signatures --compact --width --limit --name-only --exact --no-truncate --color --quiet --noprogress --verbose --json --aur --aur-search --no-news --timeout --paru --"
+    local s_opts="-Sy -Syy -Su -Syu -Suu -Syuu -Ss -Ssq -Si -Sii -Sl -Sw -Sp -Sg -Sc -Scc -Sd -Sdd"
+    local q_opts="-Qq -Qi -Qii -Qs -Ql -Qm -Qn -Qo -Qe -Qr -Qrr -Qu -Qdt -Qg -Qk -Qkk -Qc -Qp -Qlp --dot"
+    local r_opts="-Rs -Rss -Rn -Rc -Rd -Rdd"
     local u_opts="-Ud -Udd"
+    local f_opts="-Fy -Fyy -Fq"
 
     prev="${COMP_WORDS[COMP_CWORD-1]}"
 
     case "$prev" in
-        --root|--dbpath|--cachedir)
+        --config|--logfile)
+            COMPREPLY=( $(compgen -f -- "$cur") )
+            return
+            ;;
+        --sysroot|--root|--dbpath|--cachedir|--gpgdir)
             COMPREPLY=( $(compgen -d -- "$cur") )
             return
             ;;
-        --overwrite)
+        --overwrite|--ignore|--assume-installed|--parallel|--retries|--width|--limit|--download-warn|--since|--keep)
             COMPREPLY=()
             return
             ;;
+        --arch)
+            COMPREPLY=( $(compgen -W "auto x86_64 x86_64_v3 x86_64_v4 i686 aarch64 armv7h riscv64" -- "$cur") )
+            return
+            ;;
+        --color)
+            COMPREPLY=( $(compgen -W "auto always never" -- "$cur") )
+            return
+            ;;
+        --op)
+            COMPREPLY=( $(compgen -W "install install-local sync download remove database" -- "$cur") )
+            return
+            ;;
+        --yes-to)
+            COMPREPLY=( $(compgen -W "install remove replace conflict" -- "$cur") )
+            return
+            ;;
         history)
-            COMPREPLY=( $(compgen -W "show" -- "$cur") )
+            COMPREPLY=( $(compgen -W "show undo prune clear" -- "$cur") )
+            return
+            ;;
+        doctor)
+            COMPREPLY=( $(compgen -W "--check --list-checks --check-mirrors" -- "$cur") )
+            return
+            ;;
+        rankmirrors)
+            COMPREPLY=( $(compgen -W "--samples --timeout --retries --json" -- "$cur") )
+            return
+            ;;
+        --check)
+            COMPREPLY=( $(compgen -W "root dbpath lock cache keyring keyring-age repos distro pacnew space" -- "$cur") )
             return
             ;;
         show)
@@ -36,7 +69,7 @@ _rustpack() {
     fi
 
     if [[ " ${COMP_WORDS[*]} " == *" history "* ]]; then
-        COMPREPLY=( $(compgen -W "show" -- "$cur") )
+        COMPREPLY=( $(compgen -W "show undo prune clear --older-than --keep --op --since --raw" -- "$cur") )
         return
     fi
 
@@ -55,12 +88,17 @@ _rustpack() {
         return
     fi
 
+    if [[ " ${COMP_WORDS[*]} " == *" -F "* ]]; then
+        COMPREPLY=( $(compgen -W "$f_opts $global_opts" -- "$cur") )
+        return
+    fi
+
     if [[ " ${COMP_WORDS[*]} " == *" -U "* ]]; then
         COMPREPLY=( $(compgen -W "$u_opts $global_opts" -- "$cur") )
         return
     fi
 
-    COMPREPLY=( $(compgen -W "$ops $global_opts $s_opts $q_opts $r_opts $u_opts" -- "$cur") )
+    COMPREPLY=( $(compgen -W "$ops $global_opts $s_opts $q_opts $r_opts $u_opts $f_opts" -- "$cur") )
 }
 
 complete -F _rustpack rustpack
@@ -1,22 +1,41 @@
 # fish completion for rustpack
 
-set -l global_opts --test --dry-run --noconfirm --needed --nodeps --noscriptlet --asdeps --asexplicit --strict --insecure-skip-signatures --compact --verbose --json --aur --paru
+set -l global_opts --test --dry-run --print --time --noconfirm --needed --nodeps --noscriptlet --dbonly --disable-download-timeout --asdeps --asexplicit --strict --insecure-skip-signatures --compact --no-truncate --name-only --exact --noprogress --verbose --quiet --json --aur --aur-search --no-news --paru
 
-complete -c rustpack -f -n "__fish_use_subcommand" -a "-S -Q -R -U --why doctor history"
+complete -c rustpack -f -n "__fish_use_subcommand" -a "-S -Q -R -U -D -F --why why doctor history repos rankmirrors export import verify --clean-orphans --config-test"
 complete -c rustpack -f -l help -s h -d "Show help"
 
-complete -c rustpack -f -n "__fish_seen_subcommand_from -S" -a "-Sy -Su -Syu -Ss -Si -Sc -Scc -Sd -Sdd"
-complete -c rustpack -f -n "__fish_seen_subcommand_from -Q" -a "-Qi -Qs -Ql -Qm -Qo -Qe -Qr"
-complete -c rustpack -f -n "__fish_seen_subcommand_from -R" -a "-Rs -Rn -Rd -Rdd"
+complete -c rustpack -f -n "__fish_seen_subcommand_from -S" -a "-Sy -Syy -Su -Syu -Ss -Ssq -Si -Sii -Sl -Sw -Sp -Sg -Sc -Scc -Sd -Sdd"
+complete -c rustpack -f -n "__fish_seen_subcommand_from -Q" -a "-Qq -Qi -Qii -Qs -Ql -Qm -Qn -Qo -Qe -Qr -Qrr -Qu -Qdt -Qg -Qk -Qkk -Qc -Qp -Qlp --dot"
+complete -c rustpack -f -n "__fish_seen_subcommand_from -R" -a "-Rs -Rss -Rn -Rc -Rd -Rdd"
 complete -c rustpack -f -n "__fish_seen_subcommand_from -U" -a "-Ud -Udd"
+complete -c rustpack -f -n "__fish_seen_subcommand_from -F" -a "-Fy -Fq"
 
 for opt in $global_opts
     complete -c rustpack -f -a $opt
 end
 
+complete -c rustpack -l sysroot -r -d "Operate on the system under a directory"
+complete -c rustpack -l config -r -d "Use alternate pacman.conf"
 complete -c rustpack -f -l root -r -d "Use alternate root"
 complete -c rustpack -f -l dbpath -r -d "Use alternate db path"
 complete -c rustpack -f -l cachedir -r -d "Use alternate cache dir"
+complete -c rustpack -l logfile -r -d "Use alternate log file"
+complete -c rustpack -f -l gpgdir -r -d "Use alternate keyring dir"
+complete -c rustpack -f -l arch -x -a "auto x86_64 x86_64_v3 x86_64_v4 i686 aarch64 armv7h riscv64" -d "Use an alternate architecture"
+complete -c rustpack -f -l width -r -d "Fit package descriptions to N columns"
+complete -c rustpack -f -l timeout -r -d "Give up on fetches made by rustpack after N seconds"
+complete -c rustpack -f -n "__fish_seen_subcommand_from rankmirrors" -l samples -x -d "Fetches per mirror"
+complete -c rustpack -f -l limit -r -d "Show only the N most relevant search results"
+complete -c rustpack -f -l color -x -a "auto always never" -d "When to use colors"
 complete -c rustpack -f -l overwrite -r -d "Overwrite conflicting files"
+complete -c rustpack -f -l ignore -r -d "Hold packages back during upgrades"
+complete -c rustpack -f -l assume-installed -r -d "Treat a dependency as satisfied"
+complete -c rustpack -f -l parallel -r -d "Number of concurrent downloads"
+complete -c rustpack -f -l retries -r -d "Retry failed fetches made by rustpack"
 
-complete -c rustpack -f -n "__fish_seen_subcommand_from history" -a "show"
+complete -c rustpack -f -n "__fish_seen_subcommand_from history" -a "show undo prune clear"
+complete -c rustpack -f -n "__fish_seen_subcommand_from history" -l keep -x -d "Keep only the newest N entries"
+complete -c rustpack -f -n "__fish_seen_subcommand_from history" -l op -x -a "install install-local sync download remove database" -d "Only show one operation"
+complete -c rustpack -f -n "__fish_seen_subcommand_from history" -l since -x -d "Only show entries since YYYY-MM-DD"
+complete -c rustpack -f -n "__fish_seen_subcommand_from history" -l raw -d "Print UNIX timestamps"
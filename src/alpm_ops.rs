@@ -1,12 +1,17 @@
 use anyhow::{Context, Result, bail};
-use alpm::{Alpm, SigLevel, Usage, DownloadEvent, Progress};
+use alpm::{Alpm, Depend, SigLevel, Usage, DownloadEvent, Event, Progress, Question};
 use colored::Colorize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::io::{self, Write};
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use crate::config::{self, PacmanConfig};
+use crate::download;
+use crate::error::RustpackError;
+use crate::interrupt;
 use crate::cli::GlobalFlags;
 use crate::utils;
 
@@ -48,26 +53,27 @@ fn configure_handle(handle: &mut Alpm, config: &PacmanConfig, global: &GlobalFla
         handle.add_cachedir(config.cache_dir.as_str())?;
     }
     handle.set_check_space(config.check_space);
+    if let Some(n) = global.parallel.or(config.parallel_downloads) {
+        handle.set_parallel_downloads(n);
+    }
+    handle.set_disable_dl_timeout(global.disable_dl_timeout);
+    // Only rustpack's own fetches use it; libalpm downloads packages and databases itself
+    download::set_xfer_command(config.xfer_command.as_deref());
     
-    if let Some(ref log_file) = config.log_file {
+    if let Some(log_file) = global.log_file.as_ref().or(config.log_file.as_ref()) {
         handle.set_logfile(log_file.as_str())?;
     } else {
         handle.set_logfile("/var/log/pacman.log")?;
     }
     handle.set_use_syslog(config.use_syslog);
     
-    if let Some(ref gpg_dir) = config.gpg_dir {
+    if let Some(gpg_dir) = global.gpg_dir.as_ref().or(config.gpg_dir.as_ref()) {
         handle.set_gpgdir(gpg_dir.as_str())?;
     } else {
         handle.set_gpgdir("/etc/pacman.d/gnupg")?;
     }
     
-    let arch_for_url = if !config.architectures.is_empty() {
-        let first = config.architectures[0].as_str();
-        if first == "auto" { utils::get_arch() } else { first.to_string() }
-    } else {
-        utils::get_arch()
-    };
+    let arch_for_url = url_arch(config);
 
     let (arch_base, arch_v3, arch_v4) = utils::arch_variants(arch_for_url.as_str());
     
@@ -130,44 +136,86 @@ fn configure_handle(handle: &mut Alpm, config: &PacmanConfig, global: &GlobalFla
         }
     }
     
+    // libalpm understands the `!pattern` negation itself; order is preserved
+    for pattern in &config.no_upgrade {
+        handle.add_noupgrade(pattern.as_str())?;
+    }
+    for pattern in &config.no_extract {
+        handle.add_noextract(pattern.as_str())?;
+    }
+    
     for pattern in &global.overwrite {
         handle.add_overwrite_file(pattern.as_str())?;
     }
+    for name in config.ignore_pkgs.iter().chain(&global.ignore) {
+        handle.add_ignorepkg(name.as_str())?;
+    }
+    for group in &config.ignore_groups {
+        handle.add_ignoregroup(group.as_str())?;
+    }
+    for spec in &global.assume_installed {
+        handle.add_assume_installed(&Depend::new(spec.as_str()))?;
+    }
 
-    // Progress callbacks
-    handle.set_dl_cb(DownloadState::default(), |filename, event, state| {
-        match event.event() {
-            DownloadEvent::Init(_) => {
-                state.note_start(filename);
-            }
-            DownloadEvent::Progress(p) => {
-                if p.total > 0 {
-                    let percent = ((p.downloaded * 100) / p.total) as i32;
-                    if state.should_print(filename, percent) {
-                        let bar = progress_bar(percent, 28);
-                        let line = format!(
-                            ":: {} {} {} {}% ({}/{})",
-                            "Downloading".cyan().bold(),
-                            filename,
-                            bar,
-                            percent,
-                            format_bytes(p.downloaded),
-                            format_bytes(p.total)
-                        );
-                        print!("\r{}", line);
-                        let _ = io::stdout().flush();
-                    }
-                }
-            }
-            DownloadEvent::Completed(_) => {
-                if state.note_complete(filename) {
-                    println!("\r:: {} {}", "Downloaded".green().bold(), filename);
-                }
-            }
-            _ => {}
+    handle.set_question_cb(QuestionAnswers::new(global), |question, answers| match question.question() {
+        Question::Replace(mut q) => {
+            let accept = answers.replace
+                || answers.noconfirm
+                || utils::confirm_action(
+                    format!(
+                        ":: Replace {} with {}/{}? [Y/n] ",
+                        q.oldpkg().name(),
+                        q.newdb().name(),
+                        q.newpkg().name()
+                    )
+                    .as_str(),
+                );
+            q.set_replace(accept);
+        }
+        Question::Conflict(mut q) => {
+            let conflict = q.conflict();
+            let accept = answers.conflict
+                || (!answers.noconfirm
+                    && utils::confirm_with_default(
+                        format!(
+                            ":: {} and {} are in conflict. Remove {}? [y/N] ",
+                            conflict.package1().name(),
+                            conflict.package2().name(),
+                            conflict.package2().name()
+                        )
+                        .as_str(),
+                        false,
+                    ));
+            q.set_remove(accept);
+        }
+        _ => {}
+    });
+
+    // Downloads can still be interrupted safely; the first package change cannot
+    handle.set_event_cb((), |event, _| {
+        if matches!(event.event(), Event::TransactionStart) {
+            interrupt::committing();
         }
     });
 
+    set_download_cb(handle, global, 0);
+    if global.noprogress || !utils::stdout_is_terminal() {
+        // One plain line per finished file/package instead of redrawn bars;
+        // `\r` redraws only make sense on a terminal, not in a log or pipe
+        handle.set_progress_cb(TransState::default(), |progress, pkgname, percent, howmany, current, state| {
+            if percent >= 100 && state.should_print(progress, pkgname, percent, current, howmany) {
+                println!(
+                    ":: {} {} ({}/{})",
+                    progress_label(progress).cyan().bold(),
+                    pkgname,
+                    current,
+                    howmany
+                );
+            }
+        });
+        return Ok(());
+    }
+
     handle.set_progress_cb(TransState::default(), |progress, pkgname, percent, howmany, current, state| {
         if state.should_print(progress, pkgname, percent, current, howmany) {
             let label = progress_label(progress);
@@ -191,6 +239,69 @@ fn configure_handle(handle: &mut Alpm, config: &PacmanConfig, global: &GlobalFla
     Ok(())
 }
 
+/// Installs the download callbacks with a fresh `DownloadState`. The package
+/// download calls this again with the transaction's summed download size, so
+/// the aggregate line neither carries over the database refresh nor grows its
+/// total as each file starts.
+pub fn set_download_cb(handle: &mut Alpm, global: &GlobalFlags, expected_total: i64) {
+    let state = DownloadState { expected_total, ..Default::default() };
+    if global.noprogress || !utils::stdout_is_terminal() {
+        handle.set_dl_cb(state, |filename, event, state| match event.event() {
+            DownloadEvent::Init(_) => state.note_start(filename),
+            DownloadEvent::Completed(_) => {
+                if state.note_complete(filename) {
+                    println!(":: {} {}", "Downloaded".green().bold(), filename);
+                }
+            }
+            _ => {}
+        });
+        return;
+    }
+
+    handle.set_dl_cb(state, |filename, event, state| {
+        match event.event() {
+            DownloadEvent::Init(_) => {
+                state.note_start(filename);
+            }
+            DownloadEvent::Progress(p) => {
+                if p.total > 0 {
+                    state.note_progress(filename, p.downloaded, p.total);
+                    let percent = ((p.downloaded * 100) / p.total) as i32;
+                    if state.should_print(filename, percent) {
+                        state.redraw(None);
+                    }
+                }
+            }
+            DownloadEvent::Completed(_) => {
+                if state.note_complete(filename) {
+                    state.redraw(Some(filename));
+                }
+            }
+            _ => {}
+        }
+    });
+}
+
+fn url_arch(config: &PacmanConfig) -> String {
+    if !config.architectures.is_empty() {
+        let first = config.architectures[0].as_str();
+        if first == "auto" { utils::get_arch() } else { first.to_string() }
+    } else {
+        utils::get_arch()
+    }
+}
+
+/// Server URLs for a repository with `$repo`/`$arch` placeholders expanded
+/// exactly as they are registered with libalpm.
+pub fn expanded_servers(config: &PacmanConfig, repo: &config::Repository) -> Vec<String> {
+    let arch_for_url = url_arch(config);
+    let (_, arch_v3, arch_v4) = utils::arch_variants(arch_for_url.as_str());
+    repo.servers
+        .iter()
+        .map(|server| config::expand_server_url(server, &repo.name, &arch_for_url, &arch_v3, &arch_v4))
+        .collect()
+}
+
 fn siglevel_is_weak(raw: &str) -> bool {
     let normalized = raw.to_ascii_lowercase();
     normalized.contains("never") || !normalized.contains("required")
@@ -233,16 +344,140 @@ fn enforce_strict_config(config: &PacmanConfig, global: &GlobalFlags) -> Result<
     Ok(())
 }
 
+/// Pre-selected answers for libalpm questions, derived from `--yes-to` and `--noconfirm`.
+struct QuestionAnswers {
+    replace: bool,
+    conflict: bool,
+    noconfirm: bool,
+}
+
+impl QuestionAnswers {
+    fn new(global: &GlobalFlags) -> Self {
+        QuestionAnswers {
+            replace: global.pre_answered("replace"),
+            conflict: global.pre_answered("conflict"),
+            noconfirm: global.noconfirm,
+        }
+    }
+}
+
+/// How far back the rolling download speed looks.
+const SPEED_WINDOW: Duration = Duration::from_secs(3);
+
 #[derive(Default)]
 struct DownloadState {
     last_percent: HashMap<String, i32>,
     completed: HashMap<String, bool>,
+    /// (downloaded, total) bytes of every file seen so far
+    bytes: HashMap<String, (i64, i64)>,
+    /// Summed download size of the transaction, known before any file starts
+    expected_total: i64,
+    /// Files between start and completion, in start order; one line each
+    active: Vec<String>,
+    /// Lines of the block drawn last, which the next redraw overwrites
+    drawn: usize,
+    /// (time, downloaded bytes across all files) samples inside `SPEED_WINDOW`
+    samples: VecDeque<(Instant, i64)>,
 }
 
 impl DownloadState {
     fn note_start(&mut self, filename: &str) {
         self.last_percent.remove(filename);
         self.completed.remove(filename);
+        if let Some(entry) = self.bytes.get_mut(filename) {
+            entry.0 = 0;
+        }
+        if !self.active.iter().any(|f| f == filename) {
+            self.active.push(filename.to_string());
+        }
+    }
+
+    fn note_progress(&mut self, filename: &str, downloaded: i64, total: i64) {
+        self.bytes.insert(filename.to_string(), (downloaded, total));
+        if !self.active.iter().any(|f| f == filename) {
+            self.active.push(filename.to_string());
+        }
+        let now = Instant::now();
+        let (done, _) = self.totals();
+        self.samples.push_back((now, done));
+        while self.samples.len() > 2
+            && self.samples.front().is_some_and(|(t, _)| now.duration_since(*t) > SPEED_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    fn totals(&self) -> (i64, i64) {
+        let (done, total) = self
+            .bytes
+            .values()
+            .fold((0, 0), |(done, total), (d, t)| (done + d, total + t));
+        (done, total.max(self.expected_total))
+    }
+
+    /// Bytes per second over the samples in the window.
+    fn speed(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((t0, b0)), Some((t1, b1))) => {
+                let secs = t1.duration_since(*t0).as_secs_f64();
+                if secs < 0.2 { 0.0 } else { (b1 - b0).max(0) as f64 / secs }
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn aggregate_line(&self) -> String {
+        let (done, total) = self.totals();
+        let percent = if total > 0 { ((done * 100) / total) as i32 } else { 0 };
+        format!(
+            ":: {} {} {}% ({}/{}) {}/s",
+            "Total".cyan().bold(),
+            progress_bar(percent, 28),
+            percent,
+            format_bytes(done),
+            format_bytes(total),
+            format_bytes(self.speed() as i64)
+        )
+    }
+
+    fn file_line(&self, filename: &str) -> String {
+        let (downloaded, total) = self.bytes.get(filename).copied().unwrap_or((0, 0));
+        let percent = if total > 0 { ((downloaded * 100) / total) as i32 } else { 0 };
+        format!(
+            ":: {} {} {} {}% ({}/{})",
+            "Downloading".cyan().bold(),
+            filename,
+            progress_bar(percent, 28),
+            percent,
+            format_bytes(downloaded),
+            format_bytes(total)
+        )
+    }
+
+    /// Redraws one line per active download with the aggregate below them,
+    /// over the previous block. A `finished` file is printed above the new
+    /// block so it stays in the scrollback.
+    fn redraw(&mut self, finished: Option<&str>) {
+        let mut out = String::new();
+        if self.drawn > 0 {
+            out.push_str(format!("\x1b[{}A", self.drawn).as_str());
+        }
+        out.push('\r');
+        if let Some(filename) = finished {
+            out.push_str(format!("\x1b[K:: {} {}\n", "Downloaded".green().bold(), filename).as_str());
+        }
+        for filename in &self.active {
+            out.push_str(format!("\x1b[K{}\n", self.file_line(filename)).as_str());
+        }
+        self.drawn = 0;
+        if !self.active.is_empty() {
+            out.push_str(format!("\x1b[K{}\n", self.aggregate_line()).as_str());
+            self.drawn = self.active.len() + 1;
+        }
+        // Clears what is left of a taller previous block
+        out.push_str("\x1b[J");
+        print!("{}", out);
+        let _ = io::stdout().flush();
     }
 
     fn should_print(&mut self, filename: &str, percent: i32) -> bool {
@@ -256,6 +491,10 @@ impl DownloadState {
     }
 
     fn note_complete(&mut self, filename: &str) -> bool {
+        if let Some(entry) = self.bytes.get_mut(filename) {
+            entry.0 = entry.1;
+        }
+        self.active.retain(|f| f != filename);
         let entry = self.completed.entry(filename.to_string()).or_insert(false);
         if *entry {
             false
@@ -336,10 +575,24 @@ fn format_bytes(bytes: i64) -> String {
 }
 
 pub fn init_handle(global: &GlobalFlags) -> Result<Alpm> {
+    open_handle(global, false)
+}
+
+/// Like `init_handle`, but the sync databases are the `.files` variants used
+/// by `-F`.
+pub fn init_files_handle(global: &GlobalFlags) -> Result<Alpm> {
+    open_handle(global, true)
+}
+
+fn open_handle(global: &GlobalFlags, files_dbs: bool) -> Result<Alpm> {
     let config = effective_config(global)?;
     enforce_strict_config(&config, global)?;
     let mut handle = Alpm::new(config.root_dir.as_str(), config.db_path.as_str())
         .context("Failed to initialize libalpm handle")?;
+    if files_dbs {
+        // Must be set before the sync databases are registered
+        handle.set_dbext(".files");
+    }
     configure_handle(&mut handle, &config, global)?;
     Ok(handle)
 }
@@ -348,14 +601,18 @@ pub fn get_cache_dir(global: &GlobalFlags) -> Result<String> {
     Ok(effective_config(global)?.cache_dir)
 }
 
+/// Early hint only: libalpm takes `db.lck` itself in `trans_init` and database
+/// updates, and a lock lost to a race there surfaces as the same `DbLocked`
+/// error (`alpm::Error::HandleLock`).
 pub fn ensure_db_unlocked(global: &GlobalFlags) -> Result<()> {
     let config = effective_config(global)?;
     let lock_path = Path::new(&config.db_path).join("db.lck");
     if lock_path.exists() {
-        bail!(
+        return Err(RustpackError::DbLocked(format!(
             "database is locked (found {})",
             lock_path.to_string_lossy()
-        );
+        ))
+        .into());
     }
     Ok(())
 }
@@ -369,6 +626,20 @@ fn root_join(root: &str, rel: &str) -> String {
     }
 }
 
+/// Keyring directory the preflight and doctor checks look at: `--gpgdir` as
+/// given, otherwise the configured (or default) GPGDir under the root.
+pub fn keyring_dir(global: &GlobalFlags, config: &PacmanConfig) -> String {
+    match global.gpg_dir {
+        Some(ref dir) => dir.clone(),
+        // `effective_config` already placed it under the sysroot
+        None if global.sysroot.is_some() => config.gpg_dir.clone().unwrap_or_default(),
+        None => root_join(
+            config.root_dir.as_str(),
+            config.gpg_dir.as_deref().unwrap_or("/etc/pacman.d/gnupg"),
+        ),
+    }
+}
+
 fn detect_distro(root: &str) -> String {
     let os_release = root_join(root, "/etc/os-release");
     let content = match fs::read_to_string(os_release) {
@@ -387,30 +658,34 @@ fn detect_distro(root: &str) -> String {
 pub fn preflight_transaction(global: &GlobalFlags) -> Result<()> {
     ensure_db_unlocked(global)?;
     let config = effective_config(global)?;
-    let root = config.root_dir.as_str();
-    let gpg_dir = config.gpg_dir.as_deref().unwrap_or("/etc/pacman.d/gnupg");
-    let gpg_path = root_join(root, gpg_dir);
+    let gpg_path = keyring_dir(global, &config);
     let pubring_kbx = Path::new(&gpg_path).join("pubring.kbx");
     let pubring_gpg = Path::new(&gpg_path).join("pubring.gpg");
     let trustdb = Path::new(&gpg_path).join("trustdb.gpg");
     
     if !Path::new(&gpg_path).exists() {
-        bail!(
+        return Err(RustpackError::SignatureError(format!(
             "keyring directory missing at {} (run pacman-key --init and repopulate keyrings)",
             gpg_path
-        );
+        ))
+        .into());
     }
     if !pubring_kbx.exists() && !pubring_gpg.exists() {
-        bail!(
+        return Err(RustpackError::SignatureError(format!(
             "no keyring public keyring file in {} (expected pubring.kbx or pubring.gpg)",
             gpg_path
-        );
+        ))
+        .into());
     }
     if !trustdb.exists() {
-        bail!("keyring trustdb missing at {}", trustdb.to_string_lossy());
+        return Err(RustpackError::SignatureError(format!(
+            "keyring trustdb missing at {}",
+            trustdb.to_string_lossy()
+        ))
+        .into());
     }
     
-    let distro = detect_distro(root);
+    let distro = detect_distro(config.root_dir.as_str());
     let handle = Alpm::new(config.root_dir.as_str(), config.db_path.as_str())
         .context("Failed to initialize libalpm handle for preflight package checks")?;
     let localdb = handle.localdb();
@@ -423,8 +698,35 @@ pub fn preflight_transaction(global: &GlobalFlags) -> Result<()> {
     Ok(())
 }
 
+/// pacman.conf as parsed from `path`. A run asks for the config many times
+/// (preflight, handle setup, cache paths), so the first parse is kept and
+/// cloned; a different `path` replaces it.
+fn parsed_config(path: &str, sysroot: Option<&str>) -> Result<PacmanConfig> {
+    static CACHE: Mutex<Option<(String, Option<String>, PacmanConfig)>> = Mutex::new(None);
+    let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some((cached_path, cached_sysroot, config)) = cache.as_ref() {
+        if cached_path == path && cached_sysroot.as_deref() == sysroot {
+            return Ok(config.clone());
+        }
+    }
+    let config = config::parse_pacman_config(path, sysroot)?;
+    *cache = Some((path.to_string(), sysroot.map(|s| s.to_string()), config.clone()));
+    Ok(config)
+}
+
 pub fn effective_config(global: &GlobalFlags) -> Result<PacmanConfig> {
-    let mut config = config::parse_pacman_config("/etc/pacman.conf")?;
+    let sysroot = global.sysroot.as_deref();
+    let mut config = parsed_config(global.config_file(), sysroot)?;
+    if sysroot.is_some() {
+        // pacman.conf names paths inside the sysroot, defaults included
+        let gpg_dir = config.gpg_dir.as_deref().unwrap_or("/etc/pacman.d/gnupg");
+        config.gpg_dir = Some(config::under_sysroot(sysroot, gpg_dir));
+        let log_file = config.log_file.as_deref().unwrap_or("/var/log/pacman.log");
+        config.log_file = Some(config::under_sysroot(sysroot, log_file));
+        for hook_dir in &mut config.hook_dirs {
+            *hook_dir = config::under_sysroot(sysroot, hook_dir);
+        }
+    }
     if let Some(ref root_dir) = global.root_dir {
         config.root_dir = root_dir.clone();
     }
@@ -434,6 +736,9 @@ pub fn effective_config(global: &GlobalFlags) -> Result<PacmanConfig> {
     if let Some(ref cache_dir) = global.cache_dir {
         config.cache_dir = cache_dir.clone();
     }
+    if let Some(ref arch) = global.arch {
+        config.architectures = vec![arch.clone()];
+    }
     Ok(config)
 }
 
@@ -442,7 +747,24 @@ pub fn local_file_siglevel(global: &GlobalFlags) -> Result<SigLevel> {
     Ok(parse_siglevel(config.local_file_sig_level.as_ref()).unwrap_or(SigLevel::USE_DEFAULT))
 }
 
+/// Strips an optional `repo/` qualifier from a sync target.
+pub fn target_pkg_name(target: &str) -> &str {
+    target.split_once('/').map(|(_, name)| name).unwrap_or(target)
+}
+
 pub fn find_sync_pkg<'a>(handle: &'a Alpm, name: &str) -> Result<&'a alpm::Package> {
+    if let Some((repo, pkg_name)) = name.split_once('/') {
+        let db = handle
+            .syncdbs()
+            .iter()
+            .find(|db| db.name() == repo)
+            .ok_or_else(|| {
+                RustpackError::TargetNotFound(format!("error: repository '{}' is not configured", repo))
+            })?;
+        return db.pkg(pkg_name).map_err(|_| {
+            RustpackError::TargetNotFound(format!("error: target not found: {}", name)).into()
+        });
+    }
     for db in handle.syncdbs().iter() {
         if let Ok(pkg) = db.pkg(name) {
             return Ok(pkg);
@@ -501,11 +823,67 @@ pub fn find_sync_pkg<'a>(handle: &'a Alpm, name: &str) -> Result<&'a alpm::Packa
             msg.push_str(format!("\n  {}", m).as_str());
         }
     }
-    bail!(msg)
+    let suggestions = utils::closest_names(
+        name,
+        handle.syncdbs().iter().flat_map(|db| db.pkgs().iter().map(|pkg| pkg.name())),
+    );
+    push_did_you_mean(&mut msg, &suggestions);
+    Err(RustpackError::TargetNotFound(msg).into())
+}
+
+/// Members of a sync group, first repository wins when a package appears in
+/// several. Returns None when no configured repository defines the group.
+pub fn find_sync_group<'a>(handle: &'a Alpm, name: &str) -> Option<Vec<&'a alpm::Package>> {
+    let mut seen = HashSet::new();
+    let mut members = Vec::new();
+    let mut found = false;
+    for db in handle.syncdbs().iter() {
+        if let Ok(group) = db.group(name) {
+            found = true;
+            for pkg in group.packages().iter() {
+                if seen.insert(pkg.name().to_string()) {
+                    members.push(pkg);
+                }
+            }
+        }
+    }
+    if found { Some(members) } else { None }
 }
 
 pub fn find_local_pkg<'a>(handle: &'a Alpm, name: &str) -> Result<&'a alpm::Package> {
     let db = handle.localdb();
-    let pkg = db.pkg(name)?;
-    Ok(pkg)
+    if let Ok(pkg) = db.pkg(name) {
+        return Ok(pkg);
+    }
+    let mut msg = format!("error: package '{}' was not found", name);
+    let suggestions = utils::closest_names(name, db.pkgs().iter().map(|pkg| pkg.name()));
+    push_did_you_mean(&mut msg, &suggestions);
+    Err(RustpackError::TargetNotFound(msg).into())
+}
+
+fn push_did_you_mean(msg: &mut String, suggestions: &[String]) {
+    if !suggestions.is_empty() {
+        msg.push_str(format!("\nDid you mean: {}?", suggestions.join(", ")).as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_question_answers_follow_yes_to() {
+        let mut global = GlobalFlags::default();
+        let answers = QuestionAnswers::new(&global);
+        assert!(!answers.replace && !answers.conflict && !answers.noconfirm);
+
+        global.yes_to = vec!["replace".to_string()];
+        let answers = QuestionAnswers::new(&global);
+        assert!(answers.replace && !answers.conflict);
+
+        global.yes_to = vec!["conflict".to_string()];
+        global.noconfirm = true;
+        let answers = QuestionAnswers::new(&global);
+        assert!(!answers.replace && answers.conflict && answers.noconfirm);
+    }
 }
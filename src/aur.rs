@@ -0,0 +1,55 @@
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+use crate::download;
+
+const AUR_RPC: &str = "https://aur.archlinux.org/rpc/";
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    results: Vec<AurPackage>,
+}
+
+/// The subset of an AUR RPC (v5) search result that `-Ss --aur-search` shows.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AurPackage {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub num_votes: u32,
+    /// Set (to the flagging time) when the package is marked out of date.
+    #[serde(default)]
+    pub out_of_date: Option<i64>,
+}
+
+/// Searches AUR names and descriptions. The RPC takes a single term, so the
+/// longest query is sent and the others narrow the results locally, the way
+/// libalpm requires every query to match.
+pub fn search(queries: &[String]) -> Result<Vec<AurPackage>> {
+    let Some(term) = queries.iter().max_by_key(|q| q.len()) else {
+        return Ok(Vec::new());
+    };
+    let url = reqwest::Url::parse_with_params(AUR_RPC, &[("v", "5"), ("type", "search"), ("arg", term.as_str())])?;
+    let response: RpcResponse = download::fetch_url_json(url.as_str())?;
+    if response.kind == "error" {
+        bail!("AUR: {}", response.error.unwrap_or_else(|| "unknown error".to_string()));
+    }
+
+    let rest: Vec<String> = queries.iter().filter(|q| *q != term).map(|q| q.to_lowercase()).collect();
+    Ok(response
+        .results
+        .into_iter()
+        .filter(|pkg| {
+            let haystack = format!("{} {}", pkg.name, pkg.description.as_deref().unwrap_or("")).to_lowercase();
+            rest.iter().all(|q| haystack.contains(q.as_str()))
+        })
+        .collect())
+}
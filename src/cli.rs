@@ -1,25 +1,114 @@
+pub const YES_TO_KINDS: [&str; 4] = ["install", "remove", "replace", "conflict"];
+
+/// `--color` setting; `Auto` colors only when stdout is a terminal and
+/// `NO_COLOR` is unset.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
 #[derive(Default, Clone)]
 pub struct GlobalFlags {
     pub noconfirm: bool,
     pub needed: bool,
     pub overwrite: Vec<String>,
+    pub ignore: Vec<String>,
+    pub assume_installed: Vec<String>,
     pub asdeps: bool,
     pub asexplicit: bool,
     pub nodeps: u8,
     pub noscriptlet: bool,
+    pub dbonly: bool,
+    pub disable_dl_timeout: bool,
     pub root_dir: Option<String>,
     pub db_path: Option<String>,
     pub cache_dir: Option<String>,
+    pub log_file: Option<String>,
+    pub gpg_dir: Option<String>,
+    /// Replaces pacman.conf's `Architecture` list, e.g. to bootstrap a foreign root.
+    pub arch: Option<String>,
+    pub config_path: Option<String>,
+    /// `--sysroot`: pacman.conf's own paths (Include, GPGDir, LogFile, HookDir) resolve under it.
+    pub sysroot: Option<String>,
     pub test: bool,
+    pub print_only: bool,
     pub strict: bool,
     pub insecure_skip_signatures: bool,
     pub json: bool,
     pub compact: bool,
     pub verbose: bool,
+    pub quiet: bool,
+    pub color: ColorMode,
+    pub yes_to: Vec<String>,
+    pub explain: bool,
+    pub download_warn: Option<i64>,
+    pub width: Option<usize>,
+    pub limit: Option<usize>,
+    pub name_only: bool,
+    pub exact: bool,
+    pub no_truncate: bool,
+    pub noprogress: bool,
+    pub time: bool,
+    pub parallel: Option<u32>,
+    /// Extra attempts for failed fetches made by rustpack itself.
+    pub retries: Option<u32>,
+    /// Seconds before an AUR/news/mirror fetch is abandoned.
+    pub timeout: Option<u64>,
+}
+
+impl GlobalFlags {
+    /// pacman.conf to read: `--config`, or the system default.
+    pub fn config_file(&self) -> &str {
+        self.config_path.as_deref().unwrap_or("/etc/pacman.conf")
+    }
+
+    /// Returns true for `--overwrite '*'`, which needs a typed confirmation.
+    pub fn overwrite_all(&self) -> bool {
+        self.overwrite.iter().any(|p| p == "*")
+    }
+
+    /// Returns true when `--yes-to` pre-answers prompts of the given kind.
+    pub fn pre_answered(&self, kind: &str) -> bool {
+        self.yes_to.iter().any(|k| k == kind)
+    }
+
+    /// Width to fit package rows into, or None when output should not be truncated.
+    pub fn output_width(&self) -> Option<usize> {
+        if self.no_truncate || self.json {
+            return None;
+        }
+        self.width.or_else(crate::utils::terminal_width)
+    }
 }
 
 #[derive(Default, Clone)]
 pub struct RemoveFlags {
-    pub recursive: bool,
+    /// `-Rs` spares explicitly installed dependencies, `-Rss` removes them too.
+    pub recursive: u8,
+    pub cascade: bool,
     pub nosave: bool,
 }
+
+#[derive(Default, Clone)]
+pub struct DoctorFlags {
+    pub checks: Vec<String>,
+    pub list_checks: bool,
+    pub check_mirrors: bool,
+}
+
+#[derive(Default, Clone)]
+pub struct HistoryFlags {
+    pub older_than: Option<String>,
+    pub keep: Option<usize>,
+    pub op: Option<String>,
+    pub since: Option<String>,
+    pub raw: bool,
+}
+
+#[derive(Default, Clone)]
+pub struct MirrorFlags {
+    pub samples: Option<u32>,
+}
@@ -1,5 +1,7 @@
-use anyhow::{Result, Context};
+use anyhow::{Result, Context, bail};
+use std::collections::HashSet;
 use std::fs;
+use std::path::{Path, PathBuf};
 use regex::Regex;
 
 #[derive(Debug, Clone)]
@@ -12,10 +14,18 @@ pub struct PacmanConfig {
     pub log_file: Option<String>,
     pub use_syslog: bool,
     pub check_space: bool,
+    pub parallel_downloads: Option<u32>,
     pub architectures: Vec<String>,
     pub sig_level: Option<String>,
     pub local_file_sig_level: Option<String>,
     pub remote_file_sig_level: Option<String>,
+    /// Glob patterns in file order; a leading `!` negates, later patterns win
+    pub no_upgrade: Vec<String>,
+    pub no_extract: Vec<String>,
+    pub ignore_pkgs: Vec<String>,
+    pub ignore_groups: Vec<String>,
+    /// External downloader with `%u` (URL) and `%o` (output file) placeholders.
+    pub xfer_command: Option<String>,
     pub repositories: Vec<Repository>,
 }
 
@@ -37,77 +47,134 @@ impl Default for PacmanConfig {
             log_file: None,
             use_syslog: false,
             check_space: false,
+            parallel_downloads: None,
             architectures: Vec::new(),
             sig_level: None,
             local_file_sig_level: None,
             remote_file_sig_level: None,
+            no_upgrade: Vec::new(),
+            no_extract: Vec::new(),
+            ignore_pkgs: Vec::new(),
+            ignore_groups: Vec::new(),
+            xfer_command: None,
             repositories: Vec::new(),
         }
     }
 }
 
-pub fn parse_pacman_config(path: &str) -> Result<PacmanConfig> {
+/// Nesting limit for `Include`, matching pacman's.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Parses pacman.conf. Under `--sysroot`, `Include` targets are read from
+/// inside `sysroot` like every other path the file names.
+pub fn parse_pacman_config(path: &str, sysroot: Option<&str>) -> Result<PacmanConfig> {
     let content = fs::read_to_string(path)
         .context(format!("Failed to read {}", path))?;
     
-    let mut config = PacmanConfig::default();
-    let mut current_repo: Option<Repository> = None;
-    let mut in_options = false;
-    
-    let repo_regex = Regex::new(r"^\[([^\]]+)\]").unwrap();
-    let option_regex = Regex::new(r"^(\w+)\s*=\s*(.+)").unwrap();
+    let mut parser = ConfigParser::new(sysroot);
+    parser.include_chain.insert(canonical_path(Path::new(path)));
+    parser.parse_lines(&content, 0)?;
+    Ok(parser.finish())
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Parsing state shared across `Include`d files, so an included file
+/// continues the section it was included from.
+struct ConfigParser {
+    config: PacmanConfig,
+    current_repo: Option<Repository>,
+    in_options: bool,
+    sysroot: Option<String>,
+    /// Files currently being read, to catch `Include` loops
+    include_chain: HashSet<PathBuf>,
+    repo_regex: Regex,
+    option_regex: Regex,
+}
+
+impl ConfigParser {
+    fn new(sysroot: Option<&str>) -> Self {
+        ConfigParser {
+            config: PacmanConfig::default(),
+            current_repo: None,
+            in_options: false,
+            sysroot: sysroot.map(|s| s.to_string()),
+            include_chain: HashSet::new(),
+            repo_regex: Regex::new(r"^\[([^\]]+)\]").unwrap(),
+            option_regex: Regex::new(r"^(\w+)\s*=\s*(.+)").unwrap(),
+        }
+    }
     
-    for line in content.lines() {
-        let line = line.trim();
-        
-        // Skip comments and empty lines
-        if line.is_empty() || line.starts_with('#') {
-            continue;
+    /// Saves the last repository and hands back the parsed config.
+    fn finish(mut self) -> PacmanConfig {
+        if let Some(repo) = self.current_repo.take().filter(|r| !r.servers.is_empty()) {
+            self.config.repositories.push(repo);
         }
-        
-        // Check for repository section
-        if let Some(caps) = repo_regex.captures(line) {
-            let section_name = caps.get(1).unwrap().as_str();
+        self.config
+    }
+    
+    fn parse_lines(&mut self, content: &str, depth: usize) -> Result<()> {
+        for line in content.lines() {
+            let line = strip_inline_comment(line.trim());
             
-            // Save previous repository
-            if let Some(repo) = current_repo.take() {
-                if !repo.servers.is_empty() {
-                    config.repositories.push(repo);
-                }
+            // Skip comments and empty lines
+            if line.is_empty() || line.starts_with('#') {
+                continue;
             }
             
-            // Track [options] section
-            in_options = section_name == "options";
-            if section_name != "options" {
-                current_repo = Some(Repository {
-                    name: section_name.to_string(),
-                    servers: Vec::new(),
-                    sig_level: "Required DatabaseOptional".to_string(),
-                });
-            }
-            continue;
-        }
-        
-        if in_options {
-            // Boolean-style options
-            match line {
-                "CheckSpace" => {
-                    config.check_space = true;
-                    continue;
+            // Check for repository section
+            if let Some(caps) = self.repo_regex.captures(line) {
+                let section_name = caps.get(1).unwrap().as_str().to_string();
+                
+                // Save previous repository
+                if let Some(repo) = self.current_repo.take() {
+                    if !repo.servers.is_empty() {
+                        self.config.repositories.push(repo);
+                    }
                 }
-                "UseSyslog" => {
-                    config.use_syslog = true;
-                    continue;
+                
+                // Track [options] section
+                self.in_options = section_name == "options";
+                if section_name != "options" {
+                    self.current_repo = Some(Repository {
+                        name: section_name,
+                        servers: Vec::new(),
+                        sig_level: "Required DatabaseOptional".to_string(),
+                    });
                 }
-                _ => {}
+                continue;
             }
-        }
-        
-        // Parse options
-        if let Some(caps) = option_regex.captures(line) {
+            
+            if self.in_options {
+                // Boolean-style options
+                match line {
+                    "CheckSpace" => {
+                        self.config.check_space = true;
+                        continue;
+                    }
+                    "UseSyslog" => {
+                        self.config.use_syslog = true;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            
+            // Parse options
+            let Some(caps) = self.option_regex.captures(line) else {
+                continue;
+            };
             let key = caps.get(1).unwrap().as_str();
             let value = caps.get(2).unwrap().as_str();
+            if key == "Include" {
+                self.include(value, depth)?;
+                continue;
+            }
             
+            let in_options = self.in_options;
+            let config = &mut self.config;
             match key {
                 "RootDir" => config.root_dir = value.to_string(),
                 "DBPath" => config.db_path = value.to_string(),
@@ -115,6 +182,10 @@ pub fn parse_pacman_config(path: &str) -> Result<PacmanConfig> {
                 "HookDir" if in_options => config.hook_dirs.push(value.to_string()),
                 "GPGDir" if in_options => config.gpg_dir = Some(value.to_string()),
                 "LogFile" if in_options => config.log_file = Some(value.to_string()),
+                "ParallelDownloads" if in_options => {
+                    config.parallel_downloads = value.trim().parse::<u32>().ok().filter(|n| *n > 0)
+                }
+                "XferCommand" if in_options => config.xfer_command = Some(value.to_string()),
                 "Architecture" if in_options => config.architectures.push(value.to_string()),
                 "SigLevel" if in_options => config.sig_level = Some(value.to_string()),
                 "LocalFileSigLevel" if in_options => {
@@ -123,40 +194,152 @@ pub fn parse_pacman_config(path: &str) -> Result<PacmanConfig> {
                 "RemoteFileSigLevel" if in_options => {
                     config.remote_file_sig_level = Some(value.to_string())
                 }
+                "NoUpgrade" if in_options => {
+                    config.no_upgrade.extend(value.split_whitespace().map(|s| s.to_string()))
+                }
+                "NoExtract" if in_options => {
+                    config.no_extract.extend(value.split_whitespace().map(|s| s.to_string()))
+                }
+                "IgnorePkg" if in_options => {
+                    config.ignore_pkgs.extend(value.split_whitespace().map(|s| s.to_string()))
+                }
+                "IgnoreGroup" if in_options => {
+                    config.ignore_groups.extend(value.split_whitespace().map(|s| s.to_string()))
+                }
                 "Server" => {
-                    if let Some(ref mut repo) = current_repo {
+                    if let Some(ref mut repo) = self.current_repo {
                         repo.servers.push(value.to_string());
                     }
                 }
-                "Include" => {
-                    // Parse included mirrorlist file
-                    if let Some(ref mut repo) = current_repo {
-                        if let Ok(servers) = parse_mirrorlist(value) {
-                            repo.servers.extend(servers);
-                        }
-                    }
-                }
                 "SigLevel" => {
-                    if let Some(ref mut repo) = current_repo {
+                    if let Some(ref mut repo) = self.current_repo {
                         repo.sig_level = value.to_string();
                     }
                 }
                 _ => {}
             }
         }
+        Ok(())
+    }
+    
+    /// Reads the files an `Include` matches in the current section:
+    /// mirrorlists inside a repository, option drop-ins inside `[options]`.
+    /// Unreadable files are skipped; loops and runaway nesting are errors.
+    fn include(&mut self, pattern: &str, depth: usize) -> Result<()> {
+        if depth + 1 > MAX_INCLUDE_DEPTH {
+            bail!("Include nested more than {} levels deep at {}", MAX_INCLUDE_DEPTH, pattern);
+        }
+        for path in expand_include(&under_sysroot(self.sysroot.as_deref(), pattern)) {
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let key = canonical_path(&path);
+            if !self.include_chain.insert(key.clone()) {
+                bail!("Include loop detected: {} includes itself", path.display());
+            }
+            let result = self.parse_lines(&content, depth + 1);
+            self.include_chain.remove(&key);
+            result?;
+        }
+        Ok(())
     }
+}
+
+/// Places an absolute path named by pacman.conf under `--sysroot`, if set.
+pub fn under_sysroot(sysroot: Option<&str>, path: &str) -> String {
+    match sysroot {
+        Some(root) => format!("{}/{}", root.trim_end_matches('/'), path.trim_start_matches('/')),
+        None => path.to_string(),
+    }
+}
+
+/// Expands an `Include` value the way pacman does: glob patterns such as
+/// `/etc/pacman.d/*.conf` yield every matching file in sorted order, and a
+/// pattern that matches nothing yields nothing.
+pub fn expand_include(pattern: &str) -> Vec<PathBuf> {
+    let Ok(paths) = glob::glob(pattern) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = paths.filter_map(|p| p.ok()).filter(|p| p.is_file()).collect();
+    paths.sort();
+    paths
+}
+
+/// Raw structure of pacman.conf as written, before empty repositories are
+/// dropped and unreadable `Include`s are skipped by `parse_pacman_config`.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOutline {
+    pub repo_sections: Vec<String>,
+    /// (section, included path)
+    pub includes: Vec<(String, String)>,
+    /// (section, SigLevel value); `options` for the global levels
+    pub sig_levels: Vec<(String, String)>,
+}
+
+pub fn outline_pacman_config(path: &str) -> Result<ConfigOutline> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read {}", path))?;
+    
+    let mut outline = ConfigOutline::default();
+    let mut section = String::new();
+    let repo_regex = Regex::new(r"^\[([^\]]+)\]").unwrap();
+    let option_regex = Regex::new(r"^(\w+)\s*=\s*(.+)").unwrap();
     
-    // Save last repository
-    if let Some(repo) = current_repo {
-        if !repo.servers.is_empty() {
-            config.repositories.push(repo);
+    for line in content.lines() {
+        let line = strip_inline_comment(line.trim());
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(caps) = repo_regex.captures(line) {
+            section = caps.get(1).unwrap().as_str().to_string();
+            if section != "options" {
+                outline.repo_sections.push(section.clone());
+            }
+            continue;
+        }
+        if let Some(caps) = option_regex.captures(line) {
+            let key = caps.get(1).unwrap().as_str();
+            let value = caps.get(2).unwrap().as_str().to_string();
+            match key {
+                "Include" => outline.includes.push((section.clone(), value)),
+                "SigLevel" | "LocalFileSigLevel" | "RemoteFileSigLevel" => {
+                    outline.sig_levels.push((section.clone(), value))
+                }
+                _ => {}
+            }
         }
     }
     
-    Ok(config)
+    Ok(outline)
 }
 
-fn parse_mirrorlist(path: &str) -> Result<Vec<String>> {
+/// Drops a trailing `# comment` and the whitespace before it. A `#` only
+/// starts a comment after whitespace, so URL fragments survive.
+fn strip_inline_comment(line: &str) -> &str {
+    let cut = line
+        .char_indices()
+        .find(|&(i, c)| c == '#' && line[..i].ends_with(char::is_whitespace))
+        .map(|(i, _)| i)
+        .unwrap_or(line.len());
+    line[..cut].trim_end()
+}
+
+/// Returns the first token of a SigLevel value that pacman would not accept.
+pub fn invalid_siglevel_token(value: &str) -> Option<String> {
+    const TRUST: [&str; 5] = ["Never", "Optional", "Required", "TrustedOnly", "TrustAll"];
+    value
+        .split_whitespace()
+        .find(|token| {
+            let base = token
+                .strip_prefix("Package")
+                .or_else(|| token.strip_prefix("Database"))
+                .unwrap_or(token);
+            *token != "UseDefault" && !TRUST.contains(&base)
+        })
+        .map(|token| token.to_string())
+}
+
+pub fn parse_mirrorlist(path: &str) -> Result<Vec<String>> {
     let content = fs::read_to_string(path)?;
     let mut servers = Vec::new();
     
@@ -184,10 +367,145 @@ pub fn expand_server_url(server: &str, repo_name: &str, arch: &str, arch_v3: &st
 mod tests {
     use super::*;
     
+    /// Parses pacman.conf content without writing it to disk.
+    fn parse_str(content: &str) -> PacmanConfig {
+        let mut parser = ConfigParser::new(None);
+        parser.parse_lines(content, 0).unwrap();
+        parser.finish()
+    }
+    
     #[test]
     fn test_expand_server_url() {
         let url = "https://mirror.example.com/$repo/os/$arch";
         let expanded = expand_server_url(url, "core", "x86_64", "x86_64_v3", "x86_64_v4");
         assert_eq!(expanded, "https://mirror.example.com/core/os/x86_64");
     }
+    
+    #[test]
+    fn test_parse_no_upgrade_and_no_extract() {
+        let path = std::env::temp_dir().join(format!("rustpack-conf-test-{}.conf", std::process::id()));
+        fs::write(
+            &path,
+            "[options]\nNoUpgrade = etc/pacman.conf etc/fstab\nNoExtract = usr/share/locale/* !usr/share/locale/en*\nNoExtract = usr/share/man/*\n",
+        )
+        .unwrap();
+        let config = parse_pacman_config(path.to_str().unwrap(), None).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.no_upgrade, vec!["etc/pacman.conf", "etc/fstab"]);
+        assert_eq!(
+            config.no_extract,
+            vec!["usr/share/locale/*", "!usr/share/locale/en*", "usr/share/man/*"]
+        );
+    }
+    
+    #[test]
+    fn test_parse_ignore_pkg_and_group() {
+        let config = parse_str("[options]\nIgnorePkg = linux linux-headers\nIgnorePkg = nvidia*\nIgnoreGroup = gnome\n");
+        assert_eq!(config.ignore_pkgs, vec!["linux", "linux-headers", "nvidia*"]);
+        assert_eq!(config.ignore_groups, vec!["gnome"]);
+    }
+    
+    #[test]
+    fn test_parse_xfer_command() {
+        let config = parse_str("[options]\nXferCommand = /usr/bin/curl -L -C - -f -o %o %u\n");
+        assert_eq!(config.xfer_command.as_deref(), Some("/usr/bin/curl -L -C - -f -o %o %u"));
+    }
+    
+    #[test]
+    fn test_include_in_options_and_loops() {
+        let dir = std::env::temp_dir().join(format!("rustpack-include-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("pacman.conf");
+        let dropin = dir.join("options.conf");
+        let mirrors = dir.join("mirrorlist");
+        fs::write(
+            &main,
+            format!(
+                "[options]\nInclude = {}\n[core]\nInclude = {}\n[extra]\nInclude = {}\n",
+                dropin.display(),
+                mirrors.display(),
+                mirrors.display()
+            ),
+        )
+        .unwrap();
+        fs::write(&dropin, "CacheDir = /srv/pkg\nArchitecture = x86_64_v3\n").unwrap();
+        fs::write(&mirrors, "# comment\nServer = https://mirror.example.com/$repo/os/$arch\n").unwrap();
+        
+        let config = parse_pacman_config(main.to_str().unwrap(), None).unwrap();
+        assert_eq!(config.cache_dir, "/srv/pkg");
+        assert_eq!(config.architectures, vec!["x86_64_v3"]);
+        assert_eq!(config.repositories.len(), 2);
+        assert_eq!(config.repositories[1].servers.len(), 1);
+        
+        fs::write(&dropin, format!("Include = {}\n", main.display())).unwrap();
+        assert!(parse_pacman_config(main.to_str().unwrap(), None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_include_glob_reads_matches_in_order() {
+        let dir = std::env::temp_dir().join(format!("rustpack-glob-test-{}", std::process::id()));
+        let dropins = dir.join("mirrors.d");
+        fs::create_dir_all(&dropins).unwrap();
+        fs::write(dropins.join("20-second.conf"), "Server = https://b.example.com/$repo\n").unwrap();
+        fs::write(dropins.join("10-first.conf"), "Server = https://a.example.com/$repo\n").unwrap();
+        fs::write(dropins.join("notes.txt"), "Server = https://ignored.example.com\n").unwrap();
+        let main = dir.join("pacman.conf");
+        fs::write(
+            &main,
+            format!(
+                "[core]\nInclude = {}/*.conf\nInclude = {}/missing-*.conf\n",
+                dropins.display(),
+                dropins.display()
+            ),
+        )
+        .unwrap();
+        
+        let config = parse_pacman_config(main.to_str().unwrap(), None).unwrap();
+        assert_eq!(
+            config.repositories[0].servers,
+            vec!["https://a.example.com/$repo", "https://b.example.com/$repo"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_include_resolves_under_sysroot() {
+        let dir = std::env::temp_dir().join(format!("rustpack-sysroot-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("etc/pacman.d")).unwrap();
+        fs::write(dir.join("etc/pacman.d/mirrorlist"), "Server = https://chroot.example.com/$repo\n").unwrap();
+        let main = dir.join("etc/pacman.conf");
+        fs::write(&main, "[core]\nInclude = /etc/pacman.d/mirrorlist\n").unwrap();
+        
+        let config = parse_pacman_config(main.to_str().unwrap(), dir.to_str()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(config.repositories[0].servers, vec!["https://chroot.example.com/$repo"]);
+    }
+    
+    #[test]
+    fn test_inline_comments_and_trailing_whitespace() {
+        assert_eq!(strip_inline_comment("Architecture = x86_64   # note"), "Architecture = x86_64");
+        assert_eq!(strip_inline_comment("Server = https://example.com/#frag"), "Server = https://example.com/#frag");
+        assert_eq!(strip_inline_comment("CheckSpace\t# enabled"), "CheckSpace");
+        
+        let path = std::env::temp_dir().join(format!("rustpack-comment-test-{}.conf", std::process::id()));
+        fs::write(
+            &path,
+            "[options]\nArchitecture = x86_64   # note\nCheckSpace # on\n[core]\nServer = https://mirror.example.com/$repo/os/$arch   \n",
+        )
+        .unwrap();
+        let config = parse_pacman_config(path.to_str().unwrap(), None).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.architectures, vec!["x86_64"]);
+        assert!(config.check_space);
+        assert_eq!(config.repositories[0].servers, vec!["https://mirror.example.com/$repo/os/$arch"]);
+    }
+    
+    #[test]
+    fn test_invalid_siglevel_token() {
+        assert_eq!(invalid_siglevel_token("Required DatabaseOptional"), None);
+        assert_eq!(invalid_siglevel_token("PackageTrustAll DatabaseNever"), None);
+        assert_eq!(invalid_siglevel_token("Required Sometimes"), Some("Sometimes".to_string()));
+        assert_eq!(invalid_siglevel_token("DatabaseRequierd"), Some("DatabaseRequierd".to_string()));
+    }
 }
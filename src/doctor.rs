@@ -1,10 +1,16 @@
 use anyhow::{Result, bail};
 use colored::Colorize;
+use std::ffi::CString;
 use std::fs;
+use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::alpm_ops;
-use crate::cli::GlobalFlags;
+use crate::cli::{DoctorFlags, GlobalFlags};
+use crate::config::{self, PacmanConfig};
+use crate::download;
+use crate::install;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Distro {
@@ -13,42 +19,76 @@ enum Distro {
     Other,
 }
 
+struct CheckRecord {
+    name: &'static str,
+    status: &'static str,
+    detail: String,
+}
+
+/// Results collected from the checks; printed as `[OK]/[WARN]/[FAIL]` lines or
+/// serialized as JSON once all checks have run.
 struct Report {
     ok: usize,
     warn: usize,
     fail: usize,
-    checks: Vec<(String, &'static str)>,
-    json: bool,
+    records: Vec<CheckRecord>,
+    /// Name of the check currently running, attached to every record it adds.
+    current: &'static str,
 }
 
 impl Report {
-    fn new(json: bool) -> Self {
-        Self { ok: 0, warn: 0, fail: 0, checks: Vec::new(), json }
+    fn new(current: &'static str) -> Self {
+        Self { ok: 0, warn: 0, fail: 0, records: Vec::new(), current }
+    }
+
+    fn push(&mut self, status: &'static str, detail: &str) {
+        self.records.push(CheckRecord { name: self.current, status, detail: detail.to_string() });
     }
 
-    fn ok(&mut self, label: &str) {
+    fn ok(&mut self, detail: &str) {
         self.ok += 1;
-        self.checks.push((label.to_string(), "ok"));
-        if !self.json {
-            println!("{} {}", "[OK]".green().bold(), label);
-        }
+        self.push("ok", detail);
     }
 
-    fn warn(&mut self, label: &str) {
+    fn warn(&mut self, detail: &str) {
         self.warn += 1;
-        self.checks.push((label.to_string(), "warn"));
-        if !self.json {
-            println!("{} {}", "[WARN]".yellow().bold(), label);
-        }
+        self.push("warn", detail);
     }
 
-    fn fail(&mut self, label: &str) {
+    fn fail(&mut self, detail: &str) {
         self.fail += 1;
-        self.checks.push((label.to_string(), "fail"));
-        if !self.json {
-            println!("{} {}", "[FAIL]".red().bold(), label);
+        self.push("fail", detail);
+    }
+
+    fn print(&self) {
+        for record in &self.records {
+            let tag = match record.status {
+                "ok" => "[OK]".green().bold(),
+                "warn" => "[WARN]".yellow().bold(),
+                _ => "[FAIL]".red().bold(),
+            };
+            println!("{} {}", tag, record.detail);
         }
     }
+
+    fn summary_json(&self) -> String {
+        format!("{{\"ok\":{},\"warn\":{},\"fail\":{}}}", self.ok, self.warn, self.fail)
+    }
+
+    fn checks_json(&self) -> String {
+        self.records
+            .iter()
+            .map(|record| {
+                format!(
+                    "{{\"name\":\"{}\",\"status\":\"{}\",\"detail\":\"{}\"}}",
+                    json_escape(record.name),
+                    record.status,
+                    json_escape(&record.detail)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
 }
 
 fn json_escape(input: &str) -> String {
@@ -84,58 +124,75 @@ fn detect_distro(root: &str) -> Distro {
     Distro::Other
 }
 
-pub fn run(global: &GlobalFlags) -> Result<()> {
-    let config = alpm_ops::effective_config(global)?;
-    let mut report = Report::new(global.json);
-    let distro = detect_distro(config.root_dir.as_str());
-    let distro_name = match distro {
-        Distro::Arch => "Arch Linux",
-        Distro::CachyOS => "CachyOS",
-        Distro::Other => "Unknown/Other",
-    };
-    if !global.json {
-        println!("{}", "rustpack doctor".bold());
-        println!("Detected distro profile: {}", distro_name);
-        println!("Root: {}", config.root_dir);
-        println!("DBPath: {}", config.db_path);
-        println!("CacheDir: {}", config.cache_dir);
-        println!();
+struct Context {
+    config: PacmanConfig,
+    distro: Distro,
+    gpg_dir: PathBuf,
+}
+
+type CheckFn = fn(&Context, &mut Report);
+
+const CHECKS: &[(&str, &str, CheckFn)] = &[
+    ("root", "Root directory exists", check_root),
+    ("dbpath", "Package database path and local database exist", check_dbpath),
+    ("lock", "No stale database lock file", check_lock),
+    ("cache", "Package cache path exists", check_cache),
+    ("keyring", "GPG directory, public keyring and trustdb exist", check_keyring),
+    ("keyring-age", "Keyring packages were updated recently", check_keyring_age),
+    ("repos", "Repositories and mirrors are configured", check_repos),
+    ("distro", "Distro-specific repository layout", check_distro),
+    ("pacnew", "Unmerged .pacnew/.pacsave files under /etc", check_pacnew),
+    ("space", "Free disk space for the root and package cache", check_space),
+];
+
+pub fn list_checks() {
+    for (name, description, _) in CHECKS {
+        println!("{:<12} {}", name, description);
     }
-    
-    if Path::new(config.root_dir.as_str()).exists() {
+}
+
+fn check_root(ctx: &Context, report: &mut Report) {
+    if Path::new(ctx.config.root_dir.as_str()).exists() {
         report.ok("Root directory exists");
     } else {
         report.fail("Root directory does not exist");
     }
-    
-    if Path::new(config.db_path.as_str()).exists() {
+}
+
+fn check_dbpath(ctx: &Context, report: &mut Report) {
+    if Path::new(ctx.config.db_path.as_str()).exists() {
         report.ok("Package database path exists");
     } else {
         report.fail("Package database path does not exist");
     }
     
-    let local_db = Path::new(config.db_path.as_str()).join("local");
+    let local_db = Path::new(ctx.config.db_path.as_str()).join("local");
     if local_db.exists() {
         report.ok("Local package database exists");
     } else {
         report.fail("Local package database is missing");
     }
-    
-    let lock_path = Path::new(config.db_path.as_str()).join("db.lck");
+}
+
+fn check_lock(ctx: &Context, report: &mut Report) {
+    let lock_path = Path::new(ctx.config.db_path.as_str()).join("db.lck");
     if lock_path.exists() {
         report.warn("Database lock file exists (possible active package manager or stale lock)");
     } else {
         report.ok("No active database lock file");
     }
-    
-    if Path::new(config.cache_dir.as_str()).exists() {
+}
+
+fn check_cache(ctx: &Context, report: &mut Report) {
+    if Path::new(ctx.config.cache_dir.as_str()).exists() {
         report.ok("Package cache path exists");
     } else {
         report.warn("Package cache path is missing");
     }
-    
-    let gpg_dir = config.gpg_dir.as_deref().unwrap_or("/etc/pacman.d/gnupg");
-    let gpg_dir_path = root_join(config.root_dir.as_str(), gpg_dir);
+}
+
+fn check_keyring(ctx: &Context, report: &mut Report) {
+    let gpg_dir_path = &ctx.gpg_dir;
     if gpg_dir_path.exists() {
         report.ok("GPG directory exists");
     } else {
@@ -156,17 +213,65 @@ pub fn run(global: &GlobalFlags) -> Result<()> {
     } else {
         report.warn("Keyring trustdb.gpg not found");
     }
-    
+}
+
+const KEYRING_MAX_AGE_DAYS: i64 = 90;
+
+/// Old keyring packages miss newly added packager keys, which shows up as
+/// "invalid or corrupted package" errors during upgrades.
+fn check_keyring_age(ctx: &Context, report: &mut Report) {
+    let handle = match alpm::Alpm::new(ctx.config.root_dir.as_str(), ctx.config.db_path.as_str()) {
+        Ok(handle) => handle,
+        Err(err) => {
+            report.warn(format!("Could not open the local database to check keyring packages: {}", err).as_str());
+            return;
+        }
+    };
+    let mut keyrings = vec!["archlinux-keyring"];
+    if ctx.distro == Distro::CachyOS {
+        keyrings.push("cachyos-keyring");
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    for name in keyrings {
+        let Ok(pkg) = handle.localdb().pkg(name) else {
+            report.fail(format!("{} is not installed", name).as_str());
+            continue;
+        };
+        let Some(installed) = pkg.install_date() else {
+            report.warn(format!("{} has no recorded install date", name).as_str());
+            continue;
+        };
+        let days = (now - installed).max(0) / (24 * 60 * 60);
+        if days > KEYRING_MAX_AGE_DAYS {
+            report.warn(
+                format!(
+                    "{} {} was installed {} days ago; refresh it with: rustpack -Sy --needed {} && rustpack -Su",
+                    name,
+                    pkg.version(),
+                    days,
+                    name
+                )
+                .as_str(),
+            );
+        } else {
+            report.ok(format!("{} {} installed {} days ago", name, pkg.version(), days).as_str());
+        }
+    }
+}
+
+fn check_repos(ctx: &Context, report: &mut Report) {
+    let config = &ctx.config;
     if config.repositories.is_empty() {
         report.fail("No repositories configured");
     } else {
         report.ok("Repositories configured");
     }
     
-    let mut repo_names = Vec::new();
     let mut insecure_server_count = 0usize;
     for repo in &config.repositories {
-        repo_names.push(repo.name.to_ascii_lowercase());
         if repo.servers.is_empty() {
             report.fail(format!("Repository '{}' has no servers", repo.name).as_str());
             continue;
@@ -191,8 +296,16 @@ pub fn run(global: &GlobalFlags) -> Result<()> {
     if insecure_server_count == 0 && !config.repositories.is_empty() {
         report.ok("All repositories include HTTPS mirrors");
     }
-    
-    match distro {
+}
+
+fn check_distro(ctx: &Context, report: &mut Report) {
+    let config = &ctx.config;
+    let repo_names: Vec<String> = config
+        .repositories
+        .iter()
+        .map(|r| r.name.to_ascii_lowercase())
+        .collect();
+    match ctx.distro {
         Distro::Arch => {
             let has_core = repo_names.iter().any(|r| r == "core");
             let has_extra = repo_names.iter().any(|r| r == "extra");
@@ -222,32 +335,333 @@ pub fn run(global: &GlobalFlags) -> Result<()> {
             report.warn("Distro is not recognized as Arch/CachyOS; only generic checks were applied");
         }
     }
-    
-    if global.json {
-        let checks = report
-            .checks
-            .iter()
-            .map(|(label, status)| {
+}
+
+/// Collects `*.pacnew`/`*.pacsave` files below `dir` without following symlinks.
+fn find_pacnew_files(dir: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            find_pacnew_files(&path, found);
+        } else if path.extension().is_some_and(|ext| ext == "pacnew" || ext == "pacsave") {
+            found.push(path);
+        }
+    }
+}
+
+fn check_pacnew(ctx: &Context, report: &mut Report) {
+    const SHOWN: usize = 5;
+    let etc = root_join(ctx.config.root_dir.as_str(), "/etc");
+    let mut found = Vec::new();
+    find_pacnew_files(&etc, &mut found);
+    if found.is_empty() {
+        report.ok("No .pacnew/.pacsave files under /etc");
+        return;
+    }
+    found.sort();
+    let mut shown = found
+        .iter()
+        .take(SHOWN)
+        .map(|p| p.to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if found.len() > SHOWN {
+        shown.push_str(format!(" (and {} more)", found.len() - SHOWN).as_str());
+    }
+    report.warn(format!("{} .pacnew/.pacsave file(s) need merging: {}", found.len(), shown).as_str());
+}
+
+const SPACE_WARN_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+const SPACE_FAIL_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Bytes available to unprivileged writers on the filesystem holding `path`.
+fn free_space(path: &Path) -> Option<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+fn check_space(ctx: &Context, report: &mut Report) {
+    for (label, dir) in [("Root", ctx.config.root_dir.as_str()), ("Package cache", ctx.config.cache_dir.as_str())] {
+        let Some(free) = free_space(Path::new(dir)) else {
+            continue;
+        };
+        let text = format!("{} {} has {} free", label, dir, install::format_bytes(free as i64));
+        if free < SPACE_FAIL_BYTES {
+            report.fail(text.as_str());
+        } else if free < SPACE_WARN_BYTES {
+            report.warn(text.as_str());
+        } else {
+            report.ok(text.as_str());
+        }
+    }
+}
+
+/// Used unless `--timeout` is given, so an unreachable mirror cannot stall doctor.
+const MIRROR_TIMEOUT_SECS: u64 = 5;
+
+fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+/// `doctor --check-mirrors`: requests `<repo>.db` from the first server of each
+/// repository with a HEAD request. Off by default because it needs the network.
+fn check_mirrors(ctx: &Context, report: &mut Report) {
+    for repo in &ctx.config.repositories {
+        let Some(server) = alpm_ops::expanded_servers(&ctx.config, repo).into_iter().next() else {
+            continue;
+        };
+        if !server.starts_with("http://") && !server.starts_with("https://") {
+            continue;
+        }
+        let url = format!("{}/{}.db", server.trim_end_matches('/'), repo.name);
+        match download::head_status(&url) {
+            Ok(status) if status.is_success() => {
+                report.ok(format!("[{}] mirror {} reachable", repo.name, url_host(&server)).as_str())
+            }
+            Ok(status) => report.warn(
                 format!(
-                    "{{\"status\":\"{}\",\"label\":\"{}\"}}",
-                    status,
-                    json_escape(label)
+                    "[{}] mirror {} answered HTTP {} for {}.db",
+                    repo.name,
+                    url_host(&server),
+                    status.as_u16(),
+                    repo.name
                 )
-            })
-            .collect::<Vec<_>>()
-            .join(",");
+                .as_str(),
+            ),
+            Err(err) => report.warn(
+                format!("[{}] mirror {} unreachable: {}", repo.name, url_host(&server), err.root_cause()).as_str(),
+            ),
+        }
+    }
+}
+
+const KNOWN_ARCHES: &[&str] = &[
+    "auto", "any", "x86_64", "x86_64_v2", "x86_64_v3", "x86_64_v4", "i686", "pentium4", "aarch64", "armv7h",
+    "riscv64",
+];
+
+/// `--config-test`: validates pacman.conf only (servers, includes, siglevels,
+/// architectures) without looking at the databases or keyring.
+pub fn config_test(global: &GlobalFlags) -> Result<()> {
+    let mut report = Report::new("config");
+    if !global.json {
+        println!("{} {}", "Testing".bold(), global.config_file());
+        println!();
+    }
+    
+    let path = global.config_file();
+    let sysroot = global.sysroot.as_deref();
+    let (config, outline) = match (config::parse_pacman_config(path, sysroot), config::outline_pacman_config(path)) {
+        (Ok(config), Ok(outline)) => {
+            report.ok("Configuration file parsed");
+            (config, outline)
+        }
+        (Err(err), _) | (_, Err(err)) => {
+            report.fail(format!("Configuration file could not be read: {}", err).as_str());
+            return finish_config_test(global, &report);
+        }
+    };
+    
+    for (section, pattern) in &outline.includes {
+        let pattern = &config::under_sysroot(sysroot, pattern);
+        // Literal paths must exist; a glob that matches nothing is only noted
+        let paths = if pattern.contains(['*', '?', '[']) {
+            config::expand_include(pattern)
+        } else {
+            vec![PathBuf::from(pattern)]
+        };
+        if paths.is_empty() {
+            report.warn(format!("[{}] Include {} matches no files", section, pattern).as_str());
+        }
+        for path in paths {
+            let path = path.to_string_lossy();
+            if section == "options" {
+                match fs::read_to_string(path.as_ref()) {
+                    Ok(_) => report.ok(format!("[options] Include {}", path).as_str()),
+                    Err(err) => report.fail(format!("[options] Include {} cannot be read: {}", path, err).as_str()),
+                }
+                continue;
+            }
+            match config::parse_mirrorlist(path.as_ref()) {
+                Ok(servers) if servers.is_empty() => {
+                    report.warn(format!("[{}] Include {} lists no servers", section, path).as_str())
+                }
+                Ok(servers) => {
+                    report.ok(format!("[{}] Include {} ({} servers)", section, path, servers.len()).as_str())
+                }
+                Err(err) => report.fail(format!("[{}] Include {} cannot be read: {}", section, path, err).as_str()),
+            }
+        }
+    }
+    
+    if outline.repo_sections.is_empty() {
+        report.fail("No repositories configured");
+    }
+    for name in &outline.repo_sections {
+        match config.repositories.iter().find(|r| &r.name == name) {
+            Some(repo) => report.ok(format!("[{}] {} server(s)", name, repo.servers.len()).as_str()),
+            None => report.fail(format!("[{}] has no servers", name).as_str()),
+        }
+    }
+    
+    for (section, value) in &outline.sig_levels {
+        match config::invalid_siglevel_token(value) {
+            Some(token) => report.fail(format!("[{}] SigLevel '{}': unknown token '{}'", section, value, token).as_str()),
+            None => report.ok(format!("[{}] SigLevel '{}'", section, value).as_str()),
+        }
+    }
+    
+    for arch in config.architectures.iter().flat_map(|a| a.split_whitespace()) {
+        if KNOWN_ARCHES.contains(&arch) {
+            report.ok(format!("Architecture '{}'", arch).as_str());
+        } else {
+            report.warn(format!("Architecture '{}' is not a known pacman architecture", arch).as_str());
+        }
+    }
+    
+    finish_config_test(global, &report)
+}
+
+fn finish_config_test(global: &GlobalFlags, report: &Report) -> Result<()> {
+    if global.json {
+        println!(
+            "{{\"config\":\"{}\",\"summary\":{},\"checks\":[{}]}}",
+            json_escape(global.config_file()),
+            report.summary_json(),
+            report.checks_json()
+        );
+    } else {
+        report.print();
+        println!();
         println!(
-            "{{\"profile\":\"{}\",\"root\":\"{}\",\"dbpath\":\"{}\",\"cachedir\":\"{}\",\"summary\":{{\"ok\":{},\"warn\":{},\"fail\":{}}},\"checks\":[{}]}}",
+            "{} ok={} warn={} fail={}",
+            "Config test summary:".bold(),
+            report.ok,
+            report.warn,
+            report.fail
+        );
+    }
+    if report.fail > 0 {
+        if global.json {
+            bail!("__RUSTPACK_JSON_DOCTOR_FAILED__");
+        }
+        bail!("configuration test failed");
+    }
+    Ok(())
+}
+
+/// `-Dk`: every installed package's dependencies must be satisfied and none of
+/// its declared conflicts may be installed.
+pub fn check_database(global: &GlobalFlags) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let installed = handle.localdb().pkgs();
+    let mut report = Report::new("database");
+    
+    for pkg in installed.iter() {
+        for dep in pkg.depends().iter() {
+            if installed.find_satisfier(dep.to_string()).is_none() {
+                report.fail(format!("{} requires {} (not installed)", pkg.name(), dep).as_str());
+            }
+        }
+        for conflict in pkg.conflicts().iter() {
+            if let Some(other) = installed.find_satisfier(conflict.to_string()) {
+                if other.name() != pkg.name() {
+                    report.fail(format!("{} conflicts with installed {}", pkg.name(), other.name()).as_str());
+                }
+            }
+        }
+    }
+    if report.fail == 0 {
+        report.ok(format!("No database inconsistencies found ({} packages)", installed.len()).as_str());
+    }
+    
+    if global.json {
+        println!("{{\"summary\":{},\"checks\":[{}]}}", report.summary_json(), report.checks_json());
+    } else {
+        report.print();
+    }
+    if report.fail > 0 {
+        if global.json {
+            bail!("__RUSTPACK_JSON_DOCTOR_FAILED__");
+        }
+        bail!("{} database inconsistencies found", report.fail);
+    }
+    Ok(())
+}
+
+pub fn run(global: &GlobalFlags, flags: &DoctorFlags) -> Result<()> {
+    if flags.list_checks {
+        list_checks();
+        return Ok(());
+    }
+    for name in &flags.checks {
+        if !CHECKS.iter().any(|(n, _, _)| n == name) {
+            let names = CHECKS.iter().map(|(n, _, _)| *n).collect::<Vec<_>>();
+            bail!("unknown doctor check '{}' (available: {})", name, names.join(", "));
+        }
+    }
+    
+    let config = alpm_ops::effective_config(global)?;
+    let mut report = Report::new("doctor");
+    let distro = detect_distro(config.root_dir.as_str());
+    let distro_name = match distro {
+        Distro::Arch => "Arch Linux",
+        Distro::CachyOS => "CachyOS",
+        Distro::Other => "Unknown/Other",
+    };
+    if !global.json {
+        println!("{}", "rustpack doctor".bold());
+        println!("Detected distro profile: {}", distro_name);
+        println!("Root: {}", config.root_dir);
+        println!("DBPath: {}", config.db_path);
+        println!("CacheDir: {}", config.cache_dir);
+        println!();
+    }
+    
+    let gpg_dir = PathBuf::from(alpm_ops::keyring_dir(global, &config));
+    let ctx = Context { config, distro, gpg_dir };
+    for (name, _, check) in CHECKS {
+        if flags.checks.is_empty() || flags.checks.iter().any(|c| c == name) {
+            report.current = name;
+            check(&ctx, &mut report);
+        }
+    }
+    if flags.check_mirrors {
+        if global.timeout.is_none() {
+            download::set_timeout(MIRROR_TIMEOUT_SECS);
+        }
+        // A mirror that only answers on a retry is worth a warning
+        if global.retries.is_none() {
+            download::set_retries(0);
+        }
+        report.current = "mirrors";
+        check_mirrors(&ctx, &mut report);
+    }
+    let config = &ctx.config;
+    
+    if global.json {
+        println!(
+            "{{\"profile\":\"{}\",\"root\":\"{}\",\"dbpath\":\"{}\",\"cachedir\":\"{}\",\"summary\":{},\"checks\":[{}]}}",
             json_escape(distro_name),
             json_escape(config.root_dir.as_str()),
             json_escape(config.db_path.as_str()),
             json_escape(config.cache_dir.as_str()),
-            report.ok,
-            report.warn,
-            report.fail,
-            checks
+            report.summary_json(),
+            report.checks_json()
         );
     } else {
+        report.print();
         println!();
         println!(
             "{} ok={} warn={} fail={}",
@@ -1,59 +1,431 @@
 use anyhow::{Result, Context, bail};
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use colored::Colorize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use reqwest::{NoProxy, Proxy, StatusCode};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::RANGE;
+use sha2::{Digest, Sha256};
+
+/// Extra attempts after a transient failure; see `set_retries`.
+static RETRIES: AtomicU32 = AtomicU32::new(3);
+/// Highest `--retries` accepted.
+pub const MAX_RETRIES: u32 = 10;
+/// Delay before the first retry, doubled for every further one.
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+/// Longest wait between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Connect and idle limit in seconds; 0 keeps reqwest's default (30s).
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+/// Built on first use, after `set_timeout`, and shared by every fetch.
+static CLIENT: OnceLock<Client> = OnceLock::new();
+/// `XferCommand` from pacman.conf; when set, every fetch here runs it instead
+/// of the built-in client.
+static XFER_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+/// Numbers the scratch directories of `xfer_fetch`, which may run in parallel.
+static XFER_SCRATCH: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_retries(retries: u32) {
+    RETRIES.store(retries, Ordering::Relaxed);
+}
+
+pub fn set_timeout(secs: u64) {
+    TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+}
+
+pub fn set_xfer_command(command: Option<&str>) {
+    *XFER_COMMAND.lock().unwrap_or_else(PoisonError::into_inner) = command.map(str::to_string);
+}
+
+fn xfer_command() -> Option<String> {
+    XFER_COMMAND.lock().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// The first of `names` that is set to a non-empty value.
+fn env_value(names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+}
+
+/// `HTTP_PROXY`/`HTTPS_PROXY` (or their lowercase forms), each honoring `NO_PROXY`.
+fn env_proxies() -> Result<Vec<Proxy>> {
+    let mut proxies = Vec::new();
+    if let Some(url) = env_value(&["HTTP_PROXY", "http_proxy"]) {
+        let proxy = Proxy::http(&url).with_context(|| format!("invalid HTTP_PROXY '{}'", url))?;
+        proxies.push(proxy.no_proxy(NoProxy::from_env()));
+    }
+    if let Some(url) = env_value(&["HTTPS_PROXY", "https_proxy"]) {
+        let proxy = Proxy::https(&url).with_context(|| format!("invalid HTTPS_PROXY '{}'", url))?;
+        proxies.push(proxy.no_proxy(NoProxy::from_env()));
+    }
+    Ok(proxies)
+}
+
+fn client() -> Result<&'static Client> {
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let mut builder = Client::builder().no_proxy();
+    for proxy in env_proxies()? {
+        builder = builder.proxy(proxy);
+    }
+    let secs = TIMEOUT_SECS.load(Ordering::Relaxed);
+    if secs > 0 {
+        let timeout = Duration::from_secs(secs);
+        // The blocking client has no read_timeout: its `timeout` bounds the wait
+        // for the headers and each single read of the body, so as long as bodies
+        // are consumed through `Read` a slow but steady transfer is never cut off.
+        builder = builder.connect_timeout(timeout).timeout(timeout);
+    }
+    let client = builder.build().context("Failed to set up the HTTP client")?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+/// Connection failures, timeouts, 5xx and 429 answers may succeed on another
+/// try; anything else (a 404, a TLS error) will not.
+fn is_transient(err: &reqwest::Error) -> bool {
+    if let Some(status) = err.status() {
+        return status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+    }
+    err.is_connect() || err.is_timeout()
+}
+
+/// Sends the request `build` makes for `url`, retrying transient failures
+/// with exponential backoff.
+fn send_with_retries(url: &str, build: impl Fn(&Client) -> RequestBuilder) -> Result<Response> {
+    let client = client()?;
+    let retries = RETRIES.load(Ordering::Relaxed);
+    let mut attempt = 0;
+    loop {
+        match build(client).send().and_then(|r| r.error_for_status()) {
+            Ok(response) => return Ok(response),
+            Err(err) if attempt < retries && is_transient(&err) => {
+                let delay = (BACKOFF_BASE * 2u32.saturating_pow(attempt)).min(MAX_BACKOFF);
+                eprintln!(
+                    "{} {} ({}); retrying in {:.1}s",
+                    "warning:".yellow().bold(),
+                    url,
+                    err,
+                    delay.as_secs_f64()
+                );
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(err) => return Err(err).with_context(|| format!("Failed to download {}", url)),
+        }
+    }
+}
+
+/// GETs `url`. A non-zero `resume_from` asks for the rest of the body with a
+/// Range header.
+fn get_with_retries(url: &str, resume_from: u64) -> Result<Response> {
+    send_with_retries(url, |client| {
+        let request = client.get(url);
+        if resume_from > 0 {
+            request.header(RANGE, format!("bytes={}-", resume_from))
+        } else {
+            request
+        }
+    })
+}
+
+/// Sends a HEAD request for `url` and returns the status the server answered
+/// with, error statuses included. `XferCommand` is not used for this.
+pub fn head_status(url: &str) -> Result<StatusCode> {
+    match send_with_retries(url, |client| client.head(url)) {
+        Ok(response) => Ok(response.status()),
+        Err(err) => match err.downcast_ref::<reqwest::Error>().and_then(|e| e.status()) {
+            Some(status) => Ok(status),
+            None => Err(err),
+        },
+    }
+}
+
+fn part_path(dest_path: &Path) -> PathBuf {
+    let mut part = dest_path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Downloads into `<dest>.part` and renames it into place once complete. A
+/// `.part` left by an interrupted run is resumed with a Range request; servers
+/// that answer 200 instead of 206 get a fresh download.
 pub fn download_file(url: &str, dest_path: &Path) -> Result<()> {
-    let response = reqwest::blocking::get(url)
-        .context("Failed to download file")?;
-    
-    if !response.status().is_success() {
-        bail!("Download failed with status: {}", response.status());
+    if let Some(command) = xfer_command() {
+        return xfer_download(&command, url, dest_path);
     }
+    let part = part_path(dest_path);
+    let resume_from = fs::metadata(&part).map(|meta| meta.len()).unwrap_or(0);
     
-    let mut file = File::create(dest_path)
-        .context("Failed to create destination file")?;
+    let mut response = match get_with_retries(url, resume_from) {
+        Ok(response) => response,
+        // The partial file is as long as (or longer than) the remote one
+        Err(err)
+            if resume_from > 0
+                && err
+                    .downcast_ref::<reqwest::Error>()
+                    .and_then(|e| e.status())
+                    .is_some_and(|status| status == StatusCode::RANGE_NOT_SATISFIABLE) =>
+        {
+            fs::remove_file(&part).context("Failed to remove stale partial download")?;
+            get_with_retries(url, 0)?
+        }
+        Err(err) => return Err(err),
+    };
+    
+    let resumed = resume_from > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+    let file = if resumed {
+        OpenOptions::new().append(true).open(&part)
+    } else {
+        File::create(&part)
+    }
+    .context("Failed to create destination file")?;
     
-    let content = response.bytes().context("Failed to read response")?;
-    file.write_all(&content).context("Failed to write to file")?;
+    // Stream the body in chunks so memory use stays bounded for large packages
+    let mut writer = BufWriter::new(file);
+    io::copy(&mut response, &mut writer).context("Failed to write to file")?;
+    writer.flush().context("Failed to write to file")?;
+    drop(writer);
     
+    fs::rename(&part, dest_path).context("Failed to move download into place")?;
     Ok(())
 }
 
-pub fn download_database(server: &str, repo_name: &str, dest_dir: &Path) -> Result<()> {
-    let db_filename = format!("{}.db", repo_name);
-    let url = format!("{}/{}", server, db_filename);
-    let dest = dest_dir.join(&db_filename);
-    
-    println!("  Downloading {} from {}", db_filename, server);
-    download_file(&url, &dest)?;
+/// Runs `command` the way pacman runs `XferCommand`: `%u` becomes the URL and
+/// `%o` the `.part` file, renamed into place on success. Without `%o` the
+/// command runs in the destination directory and must save the file under the
+/// URL's base name. Retrying and resuming are left to the command.
+fn xfer_download(command: &str, url: &str, dest_path: &Path) -> Result<()> {
+    let part = part_path(dest_path);
+    let dir = dest_path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let output = part.to_string_lossy();
+    let mut words = command
+        .split_whitespace()
+        .map(|word| word.replace("%u", url).replace("%o", &output));
+    let Some(program) = words.next() else {
+        bail!("XferCommand is empty");
+    };
+    let status = Command::new(&program)
+        .args(words)
+        .current_dir(dir)
+        .status()
+        .with_context(|| format!("Failed to run XferCommand '{}'", program))?;
+    if !status.success() {
+        bail!("XferCommand failed for {} ({})", url, status);
+    }
     
+    let written = if command.contains("%o") {
+        part
+    } else {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        dir.join(path.rsplit('/').next().unwrap_or(path))
+    };
+    fs::rename(&written, dest_path).context("Failed to move download into place")?;
+    Ok(())
+}
+
+/// Fetches `url` through `XferCommand` into a scratch directory and returns the body.
+fn xfer_fetch(command: &str, url: &str) -> Result<Vec<u8>> {
+    let scratch = std::env::temp_dir().join(format!(
+        "rustpack-xfer-{}-{}",
+        process::id(),
+        XFER_SCRATCH.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&scratch).context("Failed to create a scratch directory")?;
+    let body = xfer_download(command, url, &scratch.join("body"))
+        .and_then(|()| fs::read(scratch.join("body")).context("Failed to read downloaded file"));
+    let _ = fs::remove_dir_all(&scratch);
+    body
+}
+
+/// Lowercase hex SHA-256 of the file at `path`.
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).context("Failed to open downloaded file")?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).context("Failed to read downloaded file")?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// `download_file`, then checks the result against `expected_sha256`. A file
+/// that does not match is deleted so it cannot be picked up later.
+pub fn download_file_checked(url: &str, dest_path: &Path, expected_sha256: &str) -> Result<()> {
+    download_file(url, dest_path)?;
+    let actual = sha256_file(dest_path)?;
+    if !actual.eq_ignore_ascii_case(expected_sha256.trim()) {
+        let _ = fs::remove_file(dest_path);
+        bail!(
+            "checksum mismatch for {}: expected sha256 {}, got {}",
+            url,
+            expected_sha256.trim(),
+            actual
+        );
+    }
     Ok(())
 }
 
+/// Reads the whole body through `Read`, where the client timeout applies to
+/// each read; `Response::text`/`json` would apply it to the whole body instead.
+fn read_body(mut response: Response, url: &str) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    response.read_to_end(&mut body).with_context(|| format!("Failed to read {}", url))?;
+    Ok(body)
+}
+
 pub fn fetch_url_text(url: &str) -> Result<String> {
-    let response = reqwest::blocking::get(url)?
-        .error_for_status()?;
-    
-    let text = response.text()?;
-    Ok(text)
+    if let Some(command) = xfer_command() {
+        return Ok(String::from_utf8_lossy(&xfer_fetch(&command, url)?).into_owned());
+    }
+    let body = read_body(get_with_retries(url, 0)?, url)?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
 }
 
 pub fn fetch_url_json<T: serde::de::DeserializeOwned>(url: &str) -> Result<T> {
-    let response = reqwest::blocking::get(url)?
-        .error_for_status()?;
-    
-    let data = response.json::<T>()?;
-    Ok(data)
+    if let Some(command) = xfer_command() {
+        let body = xfer_fetch(&command, url)?;
+        return serde_json::from_slice(&body).with_context(|| format!("Invalid JSON from {}", url));
+    }
+    let body = read_body(get_with_retries(url, 0)?, url)?;
+    serde_json::from_slice(&body).with_context(|| format!("Invalid JSON from {}", url))
+}
+
+pub struct FetchTiming {
+    /// Until the response headers arrived.
+    pub latency: Duration,
+    pub total: Duration,
+    pub bytes: u64,
+}
+
+/// Fetches `url` and discards the body, timing the response and the transfer.
+/// Through `XferCommand` only the whole run can be timed, so the latency is
+/// the total time.
+pub fn time_fetch(url: &str) -> Result<FetchTiming> {
+    let start = Instant::now();
+    if let Some(command) = xfer_command() {
+        let bytes = xfer_fetch(&command, url)?.len() as u64;
+        let total = start.elapsed();
+        return Ok(FetchTiming {
+            latency: total,
+            total,
+            bytes,
+        });
+    }
+    let mut response = get_with_retries(url, 0)?;
+    let latency = start.elapsed();
+    let bytes = io::copy(&mut response, &mut io::sink()).with_context(|| format!("Failed to read {}", url))?;
+    Ok(FetchTiming {
+        latency,
+        total: start.elapsed(),
+        bytes,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    /// Serves `body` once with a plain 200, ignoring any Range header.
+    fn serve_once(body: &'static [u8]) -> String {
+        use std::io::Read;
+        use std::net::TcpListener;
+        
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(body).unwrap();
+        });
+        format!("http://{}/core.db", addr)
+    }
+    
     #[test]
     fn test_fetch_url() {
-        let result = fetch_url_text("https://httpbin.org/get");
-        assert!(result.is_ok());
+        let result = fetch_url_text(&serve_once(b"hello"));
+        assert_eq!(result.unwrap(), "hello");
+    }
+    
+    #[test]
+    fn test_resume_falls_back_when_ranges_are_unsupported() {
+        let dir = std::env::temp_dir().join(format!("rustpack-resume-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("core.db");
+        fs::write(part_path(&dest), b"stale-prefix").unwrap();
+        
+        download_file(&serve_once(b"complete body"), &dest).unwrap();
+        let written = fs::read(&dest).unwrap();
+        let part_left = part_path(&dest).exists();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written, b"complete body");
+        assert!(!part_left);
+    }
+    
+    #[test]
+    fn test_checked_download_rejects_corrupt_file() {
+        let dir = std::env::temp_dir().join(format!("rustpack-checksum-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("core.db");
+        
+        // sha256("abc")
+        let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        download_file_checked(&serve_once(b"abc"), &dest, expected).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"abc");
+        
+        let err = download_file_checked(&serve_once(b"abd"), &dest, expected).unwrap_err();
+        let removed = !dest.exists();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(err.to_string().contains("checksum mismatch"));
+        assert!(removed);
+    }
+    
+    #[test]
+    fn test_xfer_command_substitutes_url_and_output() {
+        let dir = std::env::temp_dir().join(format!("rustpack-xfer-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.db");
+        fs::write(&source, b"from xfer").unwrap();
+        let dest = dir.join("core.db");
+        
+        xfer_download("cp %u %o", source.to_str().unwrap(), &dest).unwrap();
+        let written = fs::read(&dest).unwrap();
+        let failed = xfer_download("false %u", "http://example.invalid/core.db", &dest).is_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written, b"from xfer");
+        assert!(failed);
+    }
+    
+    #[test]
+    fn test_time_fetch_counts_body() {
+        let timing = time_fetch(&serve_once(b"0123456789")).unwrap();
+        assert_eq!(timing.bytes, 10);
+        assert!(timing.total >= timing.latency);
+    }
+    
+    #[test]
+    fn test_head_status_reports_error_statuses() {
+        use std::io::Read;
+        use std::net::TcpListener;
+        
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+        });
+        let status = head_status(&format!("http://{}/core.db", addr)).unwrap();
+        assert_eq!(status, StatusCode::NOT_FOUND);
     }
 }
@@ -0,0 +1,90 @@
+use std::fmt;
+
+/// Categorized failures surfaced to the top-level error handler.
+///
+/// Each variant carries the human-readable message of the underlying cause so
+/// callers can match on the category instead of inspecting message text.
+#[derive(Debug, Clone)]
+pub enum RustpackError {
+    DbLocked(String),
+    TargetNotFound(String),
+    SignatureError(String),
+    DownloadFailed(String),
+    DiskFull(String),
+    ArchMismatch(String),
+    DependencyError(String),
+    FileConflict(String),
+    Other(String),
+}
+
+impl RustpackError {
+    pub fn from_alpm(err: alpm::Error, message: String) -> Self {
+        use alpm::Error as E;
+        match err {
+            E::HandleLock => RustpackError::DbLocked(message),
+            E::PkgNotFound | E::PkgRepoNotFound | E::DbNotFound => RustpackError::TargetNotFound(message),
+            E::SigInvalid
+            | E::SigMissing
+            | E::PkgInvalidSig
+            | E::PkgMissingSig
+            | E::DbInvalidSig
+            | E::Gpgme => RustpackError::SignatureError(message),
+            E::Retrieve | E::ExternalDownload | E::ServerNone | E::ServerBadUrl | E::Libcurl => {
+                RustpackError::DownloadFailed(message)
+            }
+            E::DiskSpace => RustpackError::DiskFull(message),
+            E::PkgInvalidArch => RustpackError::ArchMismatch(message),
+            E::UnsatisfiedDeps | E::ConflictingDeps => RustpackError::DependencyError(message),
+            E::FileConflicts => RustpackError::FileConflict(message),
+            _ => RustpackError::Other(message),
+        }
+    }
+
+    /// Recovers the category of an error that reached the top level, whether it
+    /// was raised as a `RustpackError` or propagated directly from libalpm.
+    pub fn classify(err: &anyhow::Error) -> Self {
+        if let Some(e) = err.downcast_ref::<RustpackError>() {
+            return e.clone();
+        }
+        if let Some(e) = err.downcast_ref::<alpm::Error>() {
+            return RustpackError::from_alpm(*e, err.to_string());
+        }
+        RustpackError::Other(err.to_string())
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RustpackError::DbLocked(_) => "db_locked",
+            RustpackError::TargetNotFound(_) => "target_not_found",
+            RustpackError::SignatureError(_) => "signature_error",
+            RustpackError::DownloadFailed(_) => "download_failed",
+            RustpackError::DiskFull(_) => "disk_full",
+            RustpackError::ArchMismatch(_) => "arch_mismatch",
+            RustpackError::DependencyError(_) => "dependency_error",
+            RustpackError::FileConflict(_) => "file_conflict",
+            RustpackError::Other(_) => "other",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            RustpackError::DbLocked(m)
+            | RustpackError::TargetNotFound(m)
+            | RustpackError::SignatureError(m)
+            | RustpackError::DownloadFailed(m)
+            | RustpackError::DiskFull(m)
+            | RustpackError::ArchMismatch(m)
+            | RustpackError::DependencyError(m)
+            | RustpackError::FileConflict(m)
+            | RustpackError::Other(m) => m,
+        }
+    }
+}
+
+impl fmt::Display for RustpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for RustpackError {}
@@ -0,0 +1,102 @@
+use anyhow::{Result, bail};
+use colored::Colorize;
+use std::path::Path;
+
+use crate::alpm_ops;
+use crate::cli::GlobalFlags;
+use crate::interrupt;
+
+/// `-Fy`: downloads the `.files` databases, which carry the file lists that
+/// the regular sync databases leave out. `-Fyy` forces the download.
+pub fn refresh(global: &GlobalFlags, force: bool) -> Result<()> {
+    let mut handle = alpm_ops::init_files_handle(global)?;
+    if !global.compact {
+        println!(":: {}", "Synchronizing package file databases...".cyan().bold());
+    }
+    if global.test {
+        println!(":: {}", "--test: skipping database update".yellow());
+        return Ok(());
+    }
+    let lockfile = handle.lockfile().to_string();
+    interrupt::while_locked(&lockfile, || handle.syncdbs_mut().update(force))?;
+    Ok(())
+}
+
+/// `-F`: finds the repository packages owning each target. Targets with a `/`
+/// match a full path, bare names match the file name in any directory.
+pub fn search(global: &GlobalFlags, targets: &[String]) -> Result<()> {
+    ensure_files_dbs(global)?;
+    let handle = alpm_ops::init_files_handle(global)?;
+    let mut not_found = 0usize;
+
+    for target in targets {
+        let wanted = target.trim_start_matches('/');
+        let full_path = target.contains('/');
+        let mut found = false;
+        for db in handle.syncdbs().iter() {
+            for pkg in db.pkgs().iter() {
+                let files = pkg.files();
+                let hits: Vec<String> = files
+                    .files()
+                    .iter()
+                    .map(|file| String::from_utf8_lossy(file.name()).to_string())
+                    .filter(|name| {
+                        if full_path {
+                            name.as_str() == wanted
+                        } else {
+                            !name.ends_with('/') && name.rsplit('/').next() == Some(wanted)
+                        }
+                    })
+                    .collect();
+                if hits.is_empty() {
+                    continue;
+                }
+                found = true;
+                if global.quiet {
+                    println!("{}/{}", db.name(), pkg.name());
+                    continue;
+                }
+                println!(
+                    "{}/{} {}",
+                    db.name().magenta().bold(),
+                    pkg.name().bold(),
+                    pkg.version().to_string().green()
+                );
+                for hit in hits {
+                    println!("    /{}", hit);
+                }
+            }
+        }
+        if !found {
+            eprintln!("{} no package owns {}", "error:".red().bold(), target);
+            not_found += 1;
+        }
+    }
+
+    if not_found > 0 {
+        bail!("{} of {} target(s) are not provided by any repository package", not_found, targets.len());
+    }
+    Ok(())
+}
+
+fn ensure_files_dbs(global: &GlobalFlags) -> Result<()> {
+    let config = alpm_ops::effective_config(global)?;
+    let sync_dir = Path::new(&config.db_path).join("sync");
+    let missing: Vec<&str> = config
+        .repositories
+        .iter()
+        .filter(|repo| !sync_dir.join(format!("{}.files", repo.name)).is_file())
+        .map(|repo| repo.name.as_str())
+        .collect();
+    if missing.len() == config.repositories.len() {
+        bail!("no file databases found; run 'rustpack -Fy' as root first");
+    }
+    if !missing.is_empty() {
+        eprintln!(
+            "{} no file database for {} (run 'rustpack -Fy' to fetch it)",
+            "warning:".yellow().bold(),
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
@@ -1,21 +1,86 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use colored::Colorize;
+use std::env;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::cli::GlobalFlags;
+use crate::cli::{GlobalFlags, HistoryFlags};
+use crate::utils;
 
 #[derive(Debug, Clone)]
-struct Entry {
-    id: String,
-    ts: u64,
-    op: String,
-    status: String,
-    targets: String,
-    summary: String,
+pub struct Entry {
+    pub id: String,
+    pub ts: u64,
+    pub op: String,
+    pub status: String,
+    pub targets: String,
+    pub summary: String,
+    pub changes: Vec<PackageChange>,
+}
+
+/// Version of one package before and after a transaction; `None` means the
+/// package was not installed on that side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    /// Whether a removed package was explicitly installed; `None` for other
+    /// changes and for entries written before this was recorded.
+    pub explicit: Option<bool>,
+}
+
+impl PackageChange {
+    /// `name=old>new` with `-` for a missing side, plus `/e` or `/d` for the
+    /// install reason of a removed package; none of these characters can
+    /// appear in package names or versions.
+    fn encode(&self) -> String {
+        let reason = match self.explicit {
+            Some(true) => "/e",
+            Some(false) => "/d",
+            None => "",
+        };
+        format!(
+            "{}={}>{}{}",
+            self.name,
+            self.old.as_deref().unwrap_or("-"),
+            self.new.as_deref().unwrap_or("-"),
+            reason
+        )
+    }
+
+    fn decode(input: &str) -> Option<Self> {
+        let (name, versions) = input.split_once('=')?;
+        let (old, new) = versions.split_once('>')?;
+        let (new, explicit) = match new.split_once('/') {
+            Some((new, "e")) => (new, Some(true)),
+            Some((new, "d")) => (new, Some(false)),
+            Some(_) => return None,
+            None => (new, None),
+        };
+        let side = |v: &str| if v == "-" { None } else { Some(v.to_string()) };
+        Some(Self { name: name.to_string(), old: side(old), new: side(new), explicit })
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} {} -> {}",
+            self.name,
+            self.old.as_deref().unwrap_or("(none)"),
+            self.new.as_deref().unwrap_or("(none)")
+        )
+    }
+}
+
+fn encode_changes(changes: &[PackageChange]) -> String {
+    changes.iter().map(PackageChange::encode).collect::<Vec<_>>().join(" ")
+}
+
+fn decode_changes(field: &str) -> Vec<PackageChange> {
+    field.split_whitespace().filter_map(PackageChange::decode).collect()
 }
 
 fn json_escape(input: &str) -> String {
@@ -27,15 +92,36 @@ fn json_escape(input: &str) -> String {
         .replace('\t', "\\t")
 }
 
+fn json_opt(value: Option<&str>) -> String {
+    match value {
+        Some(v) => format!("\"{}\"", json_escape(v)),
+        None => "null".to_string(),
+    }
+}
+
 fn entry_json(entry: &Entry) -> String {
+    let changes = entry
+        .changes
+        .iter()
+        .map(|c| {
+            format!(
+                "{{\"name\":\"{}\",\"old\":{},\"new\":{}}}",
+                json_escape(&c.name),
+                json_opt(c.old.as_deref()),
+                json_opt(c.new.as_deref())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
     format!(
-        "{{\"id\":\"{}\",\"ts\":{},\"op\":\"{}\",\"status\":\"{}\",\"targets\":\"{}\",\"summary\":\"{}\"}}",
+        "{{\"id\":\"{}\",\"ts\":{},\"op\":\"{}\",\"status\":\"{}\",\"targets\":\"{}\",\"summary\":\"{}\",\"changes\":[{}]}}",
         json_escape(&entry.id),
         entry.ts,
         json_escape(&entry.op),
         json_escape(&entry.status),
         json_escape(&entry.targets),
-        json_escape(&entry.summary)
+        json_escape(&entry.summary),
+        changes
     )
 }
 
@@ -78,23 +164,46 @@ fn now_secs() -> u64 {
         .as_secs()
 }
 
+const SYSTEM_HISTORY_DIR: &str = "/var/log/rustpack";
+
+/// Per-user history location for unprivileged runs: `$XDG_STATE_HOME/rustpack`,
+/// falling back to `~/.local/state/rustpack`.
+fn user_history_dir() -> Option<PathBuf> {
+    if let Some(state) = env::var_os("XDG_STATE_HOME").filter(|v| !v.is_empty()) {
+        return Some(PathBuf::from(state).join("rustpack"));
+    }
+    let home = env::var_os("HOME").filter(|v| !v.is_empty())?;
+    Some(PathBuf::from(home).join(".local/state/rustpack"))
+}
+
+fn uses_user_history(global: &GlobalFlags) -> bool {
+    global.root_dir.is_none() && !utils::is_root()
+}
+
 fn history_dir(global: &GlobalFlags) -> PathBuf {
     if let Some(root) = global.root_dir.as_ref() {
         if root == "/" {
-            return Path::new("/var/log/rustpack").to_path_buf();
+            return Path::new(SYSTEM_HISTORY_DIR).to_path_buf();
         }
         return Path::new(root).join("var/log/rustpack");
     }
-    Path::new("/var/log/rustpack").to_path_buf()
+    if uses_user_history(global) {
+        if let Some(dir) = user_history_dir() {
+            return dir;
+        }
+    }
+    Path::new(SYSTEM_HISTORY_DIR).to_path_buf()
 }
 
 fn history_file(global: &GlobalFlags) -> PathBuf {
     history_dir(global).join("history.log")
 }
 
+/// Parses one log line. Lines written before package versions were tracked
+/// have six fields; current lines add the encoded changes as a seventh.
 fn parse_entry(line: &str) -> Option<Entry> {
-    let parts: Vec<&str> = line.splitn(6, '|').collect();
-    if parts.len() != 6 {
+    let parts: Vec<&str> = line.splitn(7, '|').collect();
+    if parts.len() < 6 {
         return None;
     }
     let ts = parts[1].parse::<u64>().ok()?;
@@ -105,11 +214,11 @@ fn parse_entry(line: &str) -> Option<Entry> {
         status: unescape(parts[3]),
         targets: unescape(parts[4]),
         summary: unescape(parts[5]),
+        changes: parts.get(6).map(|f| decode_changes(&unescape(f))).unwrap_or_default(),
     })
 }
 
-fn read_entries(global: &GlobalFlags) -> Result<Vec<Entry>> {
-    let file = history_file(global);
+fn read_entries_from(file: &Path) -> Result<Vec<Entry>> {
     if !file.exists() {
         return Ok(Vec::new());
     }
@@ -117,12 +226,43 @@ fn read_entries(global: &GlobalFlags) -> Result<Vec<Entry>> {
     Ok(content.lines().filter_map(parse_entry).collect())
 }
 
+fn read_entries(global: &GlobalFlags) -> Result<Vec<Entry>> {
+    read_entries_from(&history_file(global))
+}
+
+/// Entries shown by `rustpack history`: unprivileged users see the system log
+/// (when readable) merged with their own per-user log, ordered by time.
+fn read_visible_entries(global: &GlobalFlags) -> Result<Vec<Entry>> {
+    let mut entries = read_entries(global)?;
+    let system_file = Path::new(SYSTEM_HISTORY_DIR).join("history.log");
+    if uses_user_history(global) && history_file(global) != system_file {
+        if let Ok(system) = read_entries_from(&system_file) {
+            entries.extend(system);
+            entries.sort_by_key(|e| e.ts);
+        }
+    }
+    Ok(entries)
+}
+
 pub fn record(
     global: &GlobalFlags,
     operation: &str,
     status: &str,
     targets: &[String],
     summary: &str,
+) -> Result<()> {
+    record_changes(global, operation, status, targets, summary, &[])
+}
+
+/// Like `record`, additionally storing per-package versions so the entry can
+/// later be reverted with `history undo`.
+pub fn record_changes(
+    global: &GlobalFlags,
+    operation: &str,
+    status: &str,
+    targets: &[String],
+    summary: &str,
+    changes: &[PackageChange],
 ) -> Result<()> {
     let dir = history_dir(global);
     fs::create_dir_all(&dir)?;
@@ -134,22 +274,216 @@ pub fn record(
     } else {
         targets.join(" ")
     };
-    let line = format!(
-        "{}|{}|{}|{}|{}|{}\n",
-        escape(&id),
-        now,
-        escape(operation),
-        escape(status),
-        escape(&target_text),
-        escape(summary)
-    );
+    let line = entry_line(&Entry {
+        id,
+        ts: now,
+        op: operation.to_string(),
+        status: status.to_string(),
+        targets: target_text,
+        summary: summary.to_string(),
+        changes: changes.to_vec(),
+    });
     let mut f = OpenOptions::new().create(true).append(true).open(file)?;
     f.write_all(line.as_bytes())?;
     Ok(())
 }
 
-pub fn show(global: &GlobalFlags, args: &[String]) -> Result<()> {
-    let entries = read_entries(global)?;
+fn entry_line(entry: &Entry) -> String {
+    format!(
+        "{}|{}|{}|{}|{}|{}|{}\n",
+        escape(&entry.id),
+        entry.ts,
+        escape(&entry.op),
+        escape(&entry.status),
+        escape(&entry.targets),
+        escape(&entry.summary),
+        escape(&encode_changes(&entry.changes))
+    )
+}
+
+/// Replaces the history log with `entries`, writing to a temporary file first
+/// and renaming it over the log so readers never observe a partial file.
+fn rewrite_entries(global: &GlobalFlags, entries: &[Entry]) -> Result<()> {
+    let file = history_file(global);
+    let tmp = file.with_extension("log.tmp");
+    {
+        let mut f = fs::File::create(&tmp)?;
+        for entry in entries {
+            f.write_all(entry_line(entry).as_bytes())?;
+        }
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, &file)?;
+    Ok(())
+}
+
+/// Formats a UNIX timestamp as local time (`YYYY-MM-DD HH:MM:SS`).
+pub fn format_local_time(ts: u64) -> String {
+    let time = ts as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return ts.to_string();
+    }
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+fn display_time(ts: u64, raw: bool) -> String {
+    if raw {
+        ts.to_string()
+    } else {
+        format_local_time(ts)
+    }
+}
+
+/// Parses `YYYY-MM-DD` into the UNIX timestamp of that day's local midnight.
+fn parse_date(input: &str) -> Option<u64> {
+    let mut parts = input.trim().splitn(3, '-');
+    let year = parts.next()?.parse::<i32>().ok()?;
+    let month = parts.next()?.parse::<i32>().ok()?;
+    let day = parts.next()?.parse::<i32>().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year - 1900;
+    tm.tm_mon = month - 1;
+    tm.tm_mday = day;
+    tm.tm_isdst = -1;
+    let ts = unsafe { libc::mktime(&mut tm) };
+    // mktime normalizes out-of-range days (2024-02-31 -> March 2nd); reject those.
+    if ts < 0 || tm.tm_mday != day {
+        return None;
+    }
+    Some(ts as u64)
+}
+
+/// Applies `--op` and `--since` before any limit is taken.
+fn filter_entries(flags: &HistoryFlags, entries: Vec<Entry>) -> Result<Vec<Entry>> {
+    let since = match flags.since.as_ref() {
+        Some(raw) => match parse_date(raw) {
+            Some(ts) => Some(ts),
+            None => bail!("invalid --since date '{}' (expected YYYY-MM-DD)", raw),
+        },
+        None => None,
+    };
+    Ok(entries
+        .into_iter()
+        .filter(|e| flags.op.as_ref().is_none_or(|op| &e.op == op))
+        .filter(|e| since.is_none_or(|ts| e.ts >= ts))
+        .collect())
+}
+
+/// Parses retention durations such as `90d`, `12w`, `36h`, `30m` or `45s`.
+fn parse_duration(input: &str) -> Option<u64> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit())?;
+    let (digits, unit) = input.split_at(split);
+    let value = digits.parse::<u64>().ok()?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    value.checked_mul(multiplier)
+}
+
+/// The system log lives under `/var/log`; only the per-user log may be
+/// rewritten without root.
+fn ensure_log_writable(global: &GlobalFlags) -> Result<()> {
+    if !utils::is_root() && !uses_user_history(global) {
+        bail!("you cannot modify {} unless you are root", history_file(global).display());
+    }
+    Ok(())
+}
+
+fn prune(global: &GlobalFlags, flags: &HistoryFlags, entries: Vec<Entry>) -> Result<()> {
+    if flags.older_than.is_none() && flags.keep.is_none() {
+        bail!("usage: rustpack history prune --older-than <duration> (e.g. 30d, 12w) | --keep <n>");
+    }
+    ensure_log_writable(global)?;
+    let before = entries.len();
+    let mut kept = entries;
+    if let Some(raw) = flags.older_than.as_ref() {
+        let Some(max_age) = parse_duration(raw) else {
+            bail!("invalid duration '{}' (expected a number followed by s, m, h, d or w)", raw);
+        };
+        let cutoff = now_secs().saturating_sub(max_age);
+        kept.retain(|e| e.ts >= cutoff);
+    }
+    if let Some(keep) = flags.keep {
+        let start = kept.len().saturating_sub(keep);
+        kept.drain(..start);
+    }
+    let removed = before - kept.len();
+    if removed > 0 {
+        rewrite_entries(global, &kept)?;
+    }
+    if global.json {
+        println!("{{\"removed\":{},\"kept\":{}}}", removed, kept.len());
+    } else {
+        println!("Pruned {} history entries ({} kept).", removed, kept.len());
+    }
+    Ok(())
+}
+
+fn clear(global: &GlobalFlags, entries: Vec<Entry>) -> Result<()> {
+    ensure_log_writable(global)?;
+    if entries.is_empty() {
+        println!("No history entries found.");
+        return Ok(());
+    }
+    let prompt = format!(
+        ":: Remove all {} entries from {}? [y/N] ",
+        entries.len(),
+        history_file(global).display()
+    );
+    if !global.noconfirm && !utils::confirm_with_default(&prompt, false) {
+        println!("History left unchanged.");
+        return Ok(());
+    }
+    rewrite_entries(global, &[])?;
+    if global.json {
+        println!("{{\"removed\":{},\"kept\":0}}", entries.len());
+    } else {
+        println!("Removed {} history entries.", entries.len());
+    }
+    Ok(())
+}
+
+/// Looks up one entry (in the same logs `rustpack history` shows) by its ID.
+pub fn find_entry(global: &GlobalFlags, id: &str) -> Result<Option<Entry>> {
+    Ok(read_visible_entries(global)?.into_iter().find(|e| e.id == id))
+}
+
+/// When `op` last completed successfully according to the history log.
+pub fn last_success(global: &GlobalFlags, op: &str) -> Option<u64> {
+    read_entries(global)
+        .ok()?
+        .iter()
+        .filter(|e| e.op == op && e.status == "success")
+        .map(|e| e.ts)
+        .max()
+}
+
+pub fn show(global: &GlobalFlags, flags: &HistoryFlags, args: &[String]) -> Result<()> {
+    if args.first().map(|a| a.as_str()) == Some("prune") {
+        return prune(global, flags, read_entries(global)?);
+    }
+    if args.first().map(|a| a.as_str()) == Some("clear") {
+        return clear(global, read_entries(global)?);
+    }
+    let entries = filter_entries(flags, read_visible_entries(global)?)?;
     if global.json {
         return show_json(&entries, args);
     }
@@ -158,7 +492,7 @@ pub fn show(global: &GlobalFlags, args: &[String]) -> Result<()> {
         return Ok(());
     }
     if args.is_empty() {
-        print_list(&entries, 20);
+        print_list(&entries, 20, flags.raw);
         return Ok(());
     }
     if args[0] == "show" {
@@ -168,20 +502,25 @@ pub fn show(global: &GlobalFlags, args: &[String]) -> Result<()> {
         }
         let id = &args[1];
         if let Some(entry) = entries.iter().find(|e| &e.id == id) {
-            print_entry(entry);
+            print_entry(entry, flags.raw);
         } else {
             println!("history entry not found: {}", id);
         }
         return Ok(());
     }
     if let Ok(limit) = args[0].parse::<usize>() {
-        print_list(&entries, limit.max(1));
+        print_list(&entries, limit.max(1), flags.raw);
         return Ok(());
     }
     println!("usage:");
     println!("  rustpack history");
     println!("  rustpack history <limit>");
+    println!("  rustpack history [--op <op>] [--since YYYY-MM-DD] [--raw]");
     println!("  rustpack history show <id>");
+    println!("  rustpack history undo <id>");
+    println!("  rustpack history prune --older-than <duration>");
+    println!("  rustpack history prune --keep <n>");
+    println!("  rustpack history clear");
     Ok(())
 }
 
@@ -228,11 +567,12 @@ fn show_json(entries: &[Entry], args: &[String]) -> Result<()> {
     Ok(())
 }
 
-fn print_list(entries: &[Entry], limit: usize) {
+fn print_list(entries: &[Entry], limit: usize, raw: bool) {
     let start = entries.len().saturating_sub(limit);
+    let ts_width = if raw { 12 } else { 19 };
     println!("{}", "Recent rustpack history".bold().cyan());
     println!(
-        "{:<20} {:<12} {:<14} {:<10} {}",
+        "{:<20} {:<ts_width$} {:<14} {:<10} {}",
         "ID".bold(),
         "TS".bold(),
         "OP".bold(),
@@ -248,9 +588,9 @@ fn print_list(entries: &[Entry], limit: usize) {
             _ => e.status.clone(),
         };
         println!(
-            "{:<20} {:<12} {:<14} {:<10} {}",
+            "{:<20} {:<ts_width$} {:<14} {:<10} {}",
             e.id,
-            e.ts,
+            display_time(e.ts, raw),
             e.op,
             status,
             e.targets
@@ -258,12 +598,70 @@ fn print_list(entries: &[Entry], limit: usize) {
     }
 }
 
-fn print_entry(entry: &Entry) {
+fn print_entry(entry: &Entry, raw: bool) {
     println!("{}", "History Entry".bold().cyan());
     println!("{} {}", "id:".bold(), entry.id);
-    println!("{} {}", "ts:".bold(), entry.ts);
+    println!("{} {}", "ts:".bold(), display_time(entry.ts, raw));
     println!("{} {}", "op:".bold(), entry.op);
     println!("{} {}", "status:".bold(), entry.status);
     println!("{} {}", "targets:".bold(), entry.targets);
     println!("{} {}", "summary:".bold(), entry.summary);
+    if !entry.changes.is_empty() {
+        println!("{}", "changes:".bold());
+        for change in &entry.changes {
+            println!("  {}", change.describe());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d"), Some(30 * 24 * 60 * 60));
+        assert_eq!(parse_duration("12w"), Some(12 * 7 * 24 * 60 * 60));
+        assert_eq!(parse_duration("90"), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("5y"), None);
+    }
+    
+    #[test]
+    fn test_entry_line_round_trips_changes() {
+        let entry = Entry {
+            id: "1700000000-42".to_string(),
+            ts: 1_700_000_000,
+            op: "sync".to_string(),
+            status: "success".to_string(),
+            targets: "vim".to_string(),
+            summary: "transaction committed".to_string(),
+            changes: vec![
+                PackageChange {
+                    name: "vim".to_string(),
+                    old: Some("9.0-1".to_string()),
+                    new: Some("1:9.1-1".to_string()),
+                    explicit: None,
+                },
+                PackageChange { name: "gpm".to_string(), old: None, new: Some("1.20-1".to_string()), explicit: None },
+                PackageChange { name: "htop".to_string(), old: Some("3.3.0-1".to_string()), new: None, explicit: Some(true) },
+                PackageChange { name: "libnl".to_string(), old: Some("3.9.0-1".to_string()), new: None, explicit: Some(false) },
+            ],
+        };
+        let parsed = parse_entry(entry_line(&entry).trim_end()).expect("line parses");
+        assert_eq!(parsed.changes, entry.changes);
+        assert_eq!(parsed.summary, entry.summary);
+        
+        let legacy = parse_entry("1-1|1|remove|success|vim|transaction committed").expect("legacy line parses");
+        assert!(legacy.changes.is_empty());
+    }
+    
+    #[test]
+    fn test_parse_date_round_trips_to_local_midnight() {
+        let ts = parse_date("2024-01-01").expect("valid date");
+        assert_eq!(format_local_time(ts), "2024-01-01 00:00:00");
+        assert_eq!(parse_date("2024-02-31"), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("yesterday"), None);
+    }
 }
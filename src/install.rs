@@ -1,16 +1,54 @@
-use anyhow::Result;
-use alpm::TransFlag;
-use std::collections::HashSet;
+use anyhow::{Result, bail};
+use alpm::{SigLevel, TransFlag};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use colored::Colorize;
 
 use crate::alpm_ops;
 use crate::cli::{GlobalFlags, RemoveFlags};
+use crate::download;
+use crate::error::RustpackError;
 use crate::history;
+use crate::interrupt;
+use crate::news;
 use crate::utils;
 
-fn format_bytes(bytes: i64) -> String {
+/// Wall-clock time spent in each transaction phase, reported with `--time`.
+struct PhaseTimes {
+    enabled: bool,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl PhaseTimes {
+    fn new(global: &GlobalFlags) -> Self {
+        Self { enabled: global.time, phases: Vec::new() }
+    }
+
+    fn measure<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        if self.enabled {
+            self.phases.push((phase, start.elapsed()));
+        }
+        result
+    }
+
+    fn print(&self) {
+        if !self.enabled || self.phases.is_empty() {
+            return;
+        }
+        println!("\n{}", "Timing".bold());
+        for (phase, elapsed) in &self.phases {
+            println!("  {:<18} {:>8.2}s", phase, elapsed.as_secs_f64());
+        }
+        let total: Duration = self.phases.iter().map(|(_, d)| *d).sum();
+        println!("  {:<18} {:>8.2}s", "total", total.as_secs_f64());
+    }
+}
+
+pub fn format_bytes(bytes: i64) -> String {
     let mut value = bytes as f64;
     let units = ["B", "KiB", "MiB", "GiB", "TiB"];
     let mut idx = 0usize;
@@ -75,6 +113,20 @@ fn print_add_summary(handle: &alpm::Alpm, global: &GlobalFlags) {
     println!("  Net Installed Size: {}", format_net_bytes(net_change));
 }
 
+/// `(old, new)` pairs for installed packages that a transaction removes because a
+/// new package lists them in its `replaces`.
+fn replacements(handle: &alpm::Alpm) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    for new in handle.trans_add().iter() {
+        for old in handle.trans_remove().iter() {
+            if new.replaces().iter().any(|dep| dep.name() == old.name()) {
+                pairs.push((old.name().to_string(), new.name().to_string()));
+            }
+        }
+    }
+    pairs
+}
+
 fn remove_summary(handle: &alpm::Alpm) -> (usize, i64) {
     let to_remove = handle.trans_remove();
     let mut remove_count = 0usize;
@@ -105,13 +157,50 @@ fn print_remove_summary(handle: &alpm::Alpm, global: &GlobalFlags) {
     println!("  Net Installed Size: {}", format_net_bytes(-reclaimed));
 }
 
-fn warn_remove_breakage(handle: &alpm::Alpm, packages: &[String], remove: &RemoveFlags) -> Result<()> {
-    if remove.recursive {
-        return Ok(());
+/// Lists packages that `-Rs` removes because they became unneeded, or that
+/// `-Rc` removes because they depend on a target, separately from the packages
+/// the user asked to remove. With `-Rss` explicitly installed dependencies are
+/// flagged, since plain `-Rs` would have kept them.
+fn print_extra_removals(handle: &alpm::Alpm, targets: &[String], remove: &RemoveFlags, global: &GlobalFlags) {
+    let (key, title) = match (remove.cascade, remove.recursive) {
+        (true, 0) => ("cascade", "Dependent packages also being removed (cascade)"),
+        (true, _) => ("cascade", "Dependents and unneeded dependencies also being removed"),
+        (false, 1) => ("unneeded", "Unneeded dependencies also being removed"),
+        _ => ("unneeded", "Dependencies also being removed, including explicitly installed ones"),
+    };
+    let mut targeted = Vec::new();
+    let mut unneeded = Vec::new();
+    for pkg in handle.trans_remove().iter() {
+        let entry = format!("{}-{}", pkg.name(), pkg.version());
+        if targets.iter().any(|t| t == pkg.name()) {
+            targeted.push(entry);
+        } else if remove.recursive > 1 && pkg.reason() == alpm::PackageReason::Explicit {
+            unneeded.push(format!("{} [explicit]", entry));
+        } else {
+            unneeded.push(entry);
+        }
+    }
+    if unneeded.is_empty() {
+        return;
     }
+    if global.compact {
+        println!("{}: {}", key, unneeded.join(" "));
+        return;
+    }
+    println!("\n{} ({})", "Targets".bold(), targeted.len());
+    for name in &targeted {
+        println!("  {}", name);
+    }
+    println!("\n{} ({})", title.bold(), unneeded.len());
+    for name in &unneeded {
+        println!("  {}", name.yellow());
+    }
+}
+
+fn dangling_dependents(handle: &alpm::Alpm, packages: &[String]) -> Result<Vec<(String, Vec<String>)>> {
     let localdb = handle.localdb();
     let targets: HashSet<&str> = packages.iter().map(|s| s.as_str()).collect();
-    let mut warned = false;
+    let mut dangling = Vec::new();
     
     for pkg_name in packages {
         let pkg = localdb
@@ -124,28 +213,207 @@ fn warn_remove_breakage(handle: &alpm::Alpm, packages: &[String], remove: &Remov
             }
         }
         if !dependents.is_empty() {
-            warned = true;
-            eprintln!(
-                "warning: removing '{}' may break dependent packages: {}",
-                pkg_name,
-                dependents.join(", ")
-            );
+            dangling.push((pkg_name.clone(), dependents));
         }
     }
-    if warned {
+    Ok(dangling)
+}
+
+fn warn_remove_breakage(handle: &alpm::Alpm, packages: &[String], remove: &RemoveFlags) -> Result<()> {
+    // -Rc removes the dependents on purpose
+    if remove.recursive > 0 || remove.cascade {
+        return Ok(());
+    }
+    let dangling = dangling_dependents(handle, packages)?;
+    for (pkg_name, dependents) in &dangling {
+        eprintln!(
+            "warning: removing '{}' may break dependent packages: {}",
+            pkg_name,
+            dependents.join(", ")
+        );
+    }
+    if !dangling.is_empty() {
         eprintln!("hint: use -Rs to remove packages with their unneeded dependencies.");
     }
     Ok(())
 }
 
+/// `-Rdd` skips every dependency check, so packages that still need the
+/// targets are left broken; spell that out and ask before continuing.
+fn confirm_nodeps_removal(handle: &alpm::Alpm, packages: &[String], global: &GlobalFlags) -> Result<bool> {
+    let dangling = dangling_dependents(handle, packages)?;
+    if dangling.is_empty() {
+        return Ok(true);
+    }
+    let mut broken: Vec<&str> = dangling
+        .iter()
+        .flat_map(|(_, dependents)| dependents.iter().map(|d| d.as_str()))
+        .collect();
+    broken.sort_unstable();
+    broken.dedup();
+    eprintln!(
+        "\n{} {}",
+        "DANGER:".red().bold(),
+        format!(
+            "-Rdd will leave {} installed package(s) with missing dependencies",
+            broken.len()
+        )
+        .red()
+        .bold()
+    );
+    for (pkg_name, dependents) in &dangling {
+        eprintln!("  {} is still required by: {}", pkg_name.bold(), dependents.join(", ").red());
+    }
+    if global.test || global.noconfirm {
+        return Ok(true);
+    }
+    Ok(utils::confirm_with_default(
+        "\n:: Remove anyway and leave these dependencies dangling? [y/N] ",
+        false,
+    ))
+}
+
+/// Roots for `--explain`: packages named by a target or brought in as members
+/// of a group target, plus every installed package being upgraded when a
+/// sysupgrade is part of the transaction.
+fn explain_roots<'a>(
+    names: &[&'a str],
+    targets: &[String],
+    sysupgrade: bool,
+    group_members: impl Fn(&str) -> Option<Vec<String>>,
+    installed: impl Fn(&str) -> bool,
+) -> Vec<&'a str> {
+    let mut requested: HashSet<String> = HashSet::new();
+    for target in targets {
+        let name = alpm_ops::target_pkg_name(target);
+        if names.contains(&name) {
+            requested.insert(name.to_string());
+        } else if let Some(members) = group_members(target) {
+            requested.extend(members);
+        }
+    }
+    names
+        .iter()
+        .copied()
+        .filter(|name| requested.contains(*name) || (sysupgrade && installed(name)))
+        .collect()
+}
+
+fn print_explain(handle: &alpm::Alpm, targets: &[String], sysupgrade: bool) {
+    let to_add = handle.trans_add();
+    let localdb = handle.localdb();
+    
+    // Resolve a dependency name to the transaction package satisfying it.
+    let mut providers: HashMap<String, String> = HashMap::new();
+    for pkg in to_add.iter() {
+        providers.insert(pkg.name().to_string(), pkg.name().to_string());
+        for provide in pkg.provides().iter() {
+            providers
+                .entry(provide.name().to_string())
+                .or_insert_with(|| pkg.name().to_string());
+        }
+    }
+    let packages: HashMap<&str, &alpm::Package> = to_add.iter().map(|p| (p.name(), p)).collect();
+    
+    let names: Vec<&str> = to_add.iter().map(|p| p.name()).collect();
+    let roots = explain_roots(
+        &names,
+        targets,
+        sysupgrade,
+        |group| {
+            alpm_ops::find_sync_group(handle, group)
+                .map(|members| members.iter().map(|p| p.name().to_string()).collect())
+        },
+        |name| localdb.pkg(name).is_ok(),
+    );
+    
+    let mut chains: HashMap<String, Vec<String>> = HashMap::new();
+    let mut required_by: HashMap<String, Vec<String>> = HashMap::new();
+    for root in &roots {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut queue: VecDeque<Vec<String>> = VecDeque::new();
+        queue.push_back(vec![root.to_string()]);
+        seen.insert(root.to_string());
+        while let Some(path) = queue.pop_front() {
+            let current = path.last().map(|s| s.as_str()).unwrap_or(root);
+            let Some(pkg) = packages.get(current) else {
+                continue;
+            };
+            for dep in pkg.depends().iter() {
+                let Some(provider) = providers.get(dep.name()) else {
+                    continue;
+                };
+                if !seen.insert(provider.clone()) {
+                    continue;
+                }
+                let mut next = path.clone();
+                next.push(provider.clone());
+                if !roots.contains(&provider.as_str()) {
+                    let entry = required_by.entry(provider.clone()).or_default();
+                    if !entry.iter().any(|r| r == root) {
+                        entry.push(root.to_string());
+                    }
+                    chains.entry(provider.clone()).or_insert_with(|| next.clone());
+                }
+                queue.push_back(next);
+            }
+        }
+    }
+    
+    let pulled: Vec<&str> = to_add
+        .iter()
+        .map(|p| p.name())
+        .filter(|name| !roots.contains(name))
+        .collect();
+    if pulled.is_empty() {
+        return;
+    }
+    println!("\n{}", "Why these packages are pulled in:".bold());
+    for name in pulled {
+        match (chains.get(name), required_by.get(name)) {
+            (Some(chain), Some(roots)) => {
+                let path = chain.iter().rev().cloned().collect::<Vec<_>>().join(" <- ");
+                println!("  {} (required by {})", path, roots.join(", "));
+            }
+            _ => println!("  {} (no dependency path from the requested targets)", name),
+        }
+    }
+}
+
+/// Releases the transaction when a step between `trans_init` and commit fails.
+/// Dropping the handle with a transaction still open leaves `db.lck` behind.
+fn release_on_err<T, E: Into<anyhow::Error>>(
+    handle: &mut alpm::Alpm,
+    result: std::result::Result<T, E>,
+) -> Result<T> {
+    result.map_err(|err| {
+        trans_end(handle);
+        err.into()
+    })
+}
+
+/// `trans_init`, telling the SIGINT handler which lock the transaction holds.
+fn trans_begin(handle: &mut alpm::Alpm, flags: TransFlag) -> Result<()> {
+    handle.trans_init(flags)?;
+    if !flags.contains(TransFlag::NO_LOCK) {
+        interrupt::lock_taken(handle.lockfile());
+    }
+    Ok(())
+}
+
+fn trans_end(handle: &mut alpm::Alpm) {
+    let _ = handle.trans_release();
+    interrupt::lock_released();
+}
+
 fn trans_prepare_or_release(handle: &mut alpm::Alpm) -> Result<()> {
-    let err_msg = match handle.trans_prepare() {
+    let failure = match handle.trans_prepare() {
         Ok(()) => None,
-        Err(err) => Some(err.to_string()),
+        Err(err) => Some((err.error(), err.to_string())),
     };
-    if let Some(msg) = err_msg {
-        let _ = handle.trans_release();
-        if msg.to_lowercase().contains("architecture") {
+    if let Some((kind, msg)) = failure {
+        trans_end(handle);
+        if kind == alpm::Error::PkgInvalidArch {
             let allowed: Vec<String> = handle
                 .architectures()
                 .iter()
@@ -165,16 +433,260 @@ fn trans_prepare_or_release(handle: &mut alpm::Alpm) -> Result<()> {
                     allowed.join(", "),
                     offenders.join(", ")
                 );
-                return Err(anyhow::anyhow!(details));
+                return Err(RustpackError::ArchMismatch(details).into());
             }
         }
-        return Err(anyhow::anyhow!(msg));
+        return Err(RustpackError::from_alpm(kind, msg).into());
     }
     Ok(())
 }
 
-pub fn install_packages(packages: &[String], global: &GlobalFlags) -> Result<()> {
+/// Files that `--overwrite '*'` would silently replace: paths already on disk
+/// that the incoming package version does not own today.
+fn overwrite_conflicts(handle: &alpm::Alpm) -> (Vec<String>, usize) {
+    let root = handle.root();
+    let localdb = handle.localdb();
+    let mut conflicts = Vec::new();
+    let mut without_filelist = 0usize;
+    // path -> installed owner, built on the first conflict only
+    let mut owners: Option<HashMap<String, String>> = None;
+    for pkg in handle.trans_add().iter() {
+        let files = pkg.files();
+        if files.files().is_empty() {
+            without_filelist += 1;
+            continue;
+        }
+        let old_files = localdb.pkg(pkg.name()).ok().map(|p| p.files());
+        for file in files.files() {
+            let name = String::from_utf8_lossy(file.name()).to_string();
+            if name.ends_with('/') {
+                continue;
+            }
+            let path = Path::new(root).join(&name);
+            if fs::symlink_metadata(&path).is_err() {
+                continue;
+            }
+            if old_files.as_ref().is_some_and(|f| f.contains(name.as_str()).is_some()) {
+                continue;
+            }
+            let owners = owners.get_or_insert_with(|| {
+                let mut map = HashMap::new();
+                for installed in localdb.pkgs().iter() {
+                    for owned in installed.files().files() {
+                        map.entry(String::from_utf8_lossy(owned.name()).to_string())
+                            .or_insert_with(|| installed.name().to_string());
+                    }
+                }
+                map
+            });
+            let owner = owners.get(&name).map(|s| s.as_str()).unwrap_or("no owner");
+            conflicts.push(format!("{} ({})", path.display(), owner));
+        }
+    }
+    (conflicts, without_filelist)
+}
+
+fn confirm_overwrite_all(handle: &alpm::Alpm, global: &GlobalFlags) -> bool {
+    eprintln!(
+        "\n{} {}",
+        "WARNING:".red().bold(),
+        "--overwrite '*' will replace ANY conflicting file on the system".red().bold()
+    );
+    let (conflicts, without_filelist) = overwrite_conflicts(handle);
+    if conflicts.is_empty() {
+        println!("  no existing files would be overwritten by packages with known file lists");
+    } else {
+        println!("{} ({})", "Files that will be overwritten".bold(), conflicts.len());
+        for conflict in &conflicts {
+            println!("  {}", conflict.yellow());
+        }
+    }
+    if without_filelist > 0 {
+        println!(
+            "  {} package(s) have no file list available; their conflicts cannot be previewed",
+            without_filelist
+        );
+    }
+    if global.test {
+        return true;
+    }
+    utils::confirm_typed("\n:: Type OVERWRITE to proceed: ", "OVERWRITE")
+}
+
+/// Enforces `--download-warn`: a transaction whose download exceeds the
+/// threshold always asks, even with `--noconfirm` or `--yes-to install`.
+fn confirm_download_size(handle: &alpm::Alpm, global: &GlobalFlags) -> bool {
+    let Some(threshold) = global.download_warn else {
+        return true;
+    };
+    let (_, _, total_download, _) = add_summary(handle);
+    if total_download <= threshold {
+        return true;
+    }
+    eprintln!(
+        "\n{} {}",
+        "warning:".yellow().bold(),
+        format!(
+            "this transaction downloads {} (above the --download-warn limit of {})",
+            format_bytes(total_download),
+            format_bytes(threshold)
+        )
+        .yellow()
+    );
+    if global.test {
+        return true;
+    }
+    utils::confirm_with_default(":: Continue with this download? [y/N] ", false)
+}
+
+fn commit_error(err: alpm::CommitError) -> anyhow::Error {
+    RustpackError::from_alpm(err.error(), err.to_string()).into()
+}
+
+/// Keeps the libalpm category of a failed `trans_add_pkg` for `--json` errors.
+fn add_error<P>(err: alpm::AddError<P>) -> anyhow::Error {
+    RustpackError::from_alpm(err.error, err.error.to_string()).into()
+}
+
+/// Warns about installed packages held back by IgnorePkg, IgnoreGroup or
+/// `--ignore` that have a newer version in the sync databases.
+fn print_ignored_upgrades(handle: &alpm::Alpm) {
+    let patterns: Vec<glob::Pattern> = handle
+        .ignorepkgs()
+        .iter()
+        .filter_map(|p| glob::Pattern::new(p).ok())
+        .collect();
+    let groups: Vec<&str> = handle.ignoregroups().iter().collect();
+    for pkg in handle.localdb().pkgs().iter() {
+        let held = patterns.iter().any(|p| p.matches(pkg.name()))
+            || pkg.groups().iter().any(|g| groups.contains(&g));
+        if !held {
+            continue;
+        }
+        if let Some(newer) = pkg.sync_new_version(handle.syncdbs()) {
+            eprintln!(
+                "{} {}: ignoring package upgrade ({} => {})",
+                "warning:".yellow().bold(),
+                pkg.name(),
+                pkg.version(),
+                newer.version()
+            );
+        }
+    }
+}
+
+/// With `--needed`, libalpm quietly drops targets that are already up to date;
+/// names the ones missing from the prepared transaction.
+fn print_needed_skips(handle: &alpm::Alpm, targets: &[String], global: &GlobalFlags) {
+    if !global.needed {
+        return;
+    }
+    let adding: HashSet<&str> = handle.trans_add().iter().map(|pkg| pkg.name()).collect();
+    for target in targets {
+        let name = target.rsplit('/').next().unwrap_or(target);
+        if adding.contains(name) {
+            continue;
+        }
+        if handle.localdb().pkg(name).is_ok() {
+            println!(" {} skipping {} (up to date)", "note:".yellow(), name);
+        }
+    }
+}
+
+/// Old and new versions of every package the prepared transaction touches,
+/// recorded so `history undo` can reverse it.
+fn transaction_changes(handle: &alpm::Alpm) -> Vec<history::PackageChange> {
+    let localdb = handle.localdb();
+    let mut changes: Vec<history::PackageChange> = handle
+        .trans_add()
+        .iter()
+        .map(|pkg| history::PackageChange {
+            name: pkg.name().to_string(),
+            old: localdb.pkg(pkg.name()).ok().map(|old| old.version().to_string()),
+            new: Some(pkg.version().to_string()),
+            explicit: None,
+        })
+        .collect();
+    for pkg in handle.trans_remove().iter() {
+        changes.push(history::PackageChange {
+            name: pkg.name().to_string(),
+            old: Some(pkg.version().to_string()),
+            new: None,
+            explicit: Some(pkg.reason() == alpm::PackageReason::Explicit),
+        });
+    }
+    changes
+}
+
+/// `--print`: lists the names the prepared transaction would install or remove,
+/// one per line, then releases it without committing.
+fn print_trans_targets(handle: &mut alpm::Alpm, removal: bool) {
+    let names: Vec<String> = if removal {
+        handle.trans_remove().iter().map(|pkg| pkg.name().to_string()).collect()
+    } else {
+        handle.trans_add().iter().map(|pkg| pkg.name().to_string()).collect()
+    };
+    for name in names {
+        println!("{}", name);
+    }
+    trans_end(handle);
+}
+
+/// Adds sync targets to the transaction, expanding names that are not packages
+/// but package groups into their members. Returns the groups that were expanded
+/// (for history), or None when the user declines a group's member list.
+fn add_sync_targets(
+    handle: &alpm::Alpm,
+    global: &GlobalFlags,
+    targets: &[String],
+    confirm_groups: bool,
+) -> Result<Option<Vec<String>>> {
+    let mut groups = Vec::new();
+    for name in targets {
+        let pkgs = match alpm_ops::find_sync_pkg(handle, name) {
+            Ok(pkg) => vec![pkg],
+            Err(err) => {
+                let Some(members) = alpm_ops::find_sync_group(handle, name) else {
+                    return Err(err);
+                };
+                if confirm_groups {
+                    println!(
+                        ":: There are {} members in group {}:",
+                        members.len(),
+                        name.bold()
+                    );
+                    let names: Vec<&str> = members.iter().map(|p| p.name()).collect();
+                    println!("   {}", names.join("  "));
+                    if !global.test
+                        && !global.noconfirm
+                        && !global.pre_answered("install")
+                        && !utils::confirm_action(format!(":: Install all members of {}? [Y/n] ", name).as_str())
+                    {
+                        return Ok(None);
+                    }
+                }
+                groups.push(format!("group {} ({} packages)", name, members.len()));
+                members
+            }
+        };
+        for pkg in pkgs {
+            handle.trans_add_pkg(pkg).map_err(add_error)?;
+        }
+    }
+    Ok(Some(groups))
+}
+
+fn proceed_prompt(download_only: bool) -> &'static str {
+    if download_only {
+        "\n:: Proceed with download? [Y/n] "
+    } else {
+        "\n:: Proceed with installation? [Y/n] "
+    }
+}
+
+pub fn install_packages(packages: &[String], global: &GlobalFlags, download_only: bool) -> Result<()> {
     let mut handle = alpm_ops::init_handle(global)?;
+    let op = if download_only { "download" } else { "install" };
     
     let mut flags = TransFlag::NONE;
     if global.needed {
@@ -189,61 +701,174 @@ pub fn install_packages(packages: &[String], global: &GlobalFlags) -> Result<()>
     if global.noscriptlet {
         flags |= TransFlag::NO_SCRIPTLET;
     }
+    if global.dbonly {
+        flags |= TransFlag::DB_ONLY;
+    }
+    if download_only {
+        flags |= TransFlag::DOWNLOAD_ONLY;
+    }
     if global.verbose {
         println!(":: verbose: operation=install targets={}", packages.join(" "));
     }
-    handle.trans_init(flags)?;
-    for name in packages {
-        let pkg = alpm_ops::find_sync_pkg(&handle, name)?;
-        handle
-            .trans_add_pkg(pkg)
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    if global.print_only {
+        flags |= TransFlag::NO_LOCK;
     }
+    trans_begin(&mut handle, flags)?;
+    let added = add_sync_targets(&handle, global, packages, !global.print_only);
+    let Some(groups) = release_on_err(&mut handle, added)? else {
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "cancelled", packages, "group installation declined");
+        return Ok(());
+    };
+    let group_note = groups.iter().map(|g| format!("; {}", g)).collect::<String>();
     
-    if !global.compact {
+    if !global.compact && !global.print_only {
         println!("{}", "resolving dependencies...".cyan());
         println!("{}", "looking for conflicting packages...".cyan());
     }
     trans_prepare_or_release(&mut handle)?;
+    if global.print_only {
+        print_trans_targets(&mut handle, false);
+        return Ok(());
+    }
+    print_needed_skips(&handle, packages, global);
     
     let to_install = handle.trans_add();
     if to_install.is_empty() {
-        let _ = handle.trans_release();
+        trans_end(&mut handle);
         println!(" {}", "there is nothing to do".yellow());
-        let _ = history::record(global, "install", "noop", packages, "no packages to install");
+        let _ = history::record(global, op, "noop", packages, "no packages to install");
         return Ok(());
     }
     print_add_summary(&handle, global);
+    if global.explain {
+        print_explain(&handle, packages, false);
+    }
+    
+    if global.overwrite_all() && !confirm_overwrite_all(&handle, global) {
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "cancelled", packages, "overwrite-all not confirmed");
+        return Ok(());
+    }
     
-    if !global.test && !global.noconfirm && !utils::confirm_action("\n:: Proceed with installation? [Y/n] ") {
-        let _ = handle.trans_release();
-        let _ = history::record(global, "install", "cancelled", packages, "user cancelled transaction");
+    if !confirm_download_size(&handle, global) {
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "cancelled", packages, "download size not confirmed");
+        return Ok(());
+    }
+    
+    if !global.test
+        && !global.noconfirm
+        && !global.pre_answered("install")
+        && !utils::confirm_action(proceed_prompt(download_only)) {
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "cancelled", packages, "user cancelled transaction");
         return Ok(());
     }
     
     if global.test {
         println!(":: {}", "--test: skipping commit".yellow());
-        let _ = handle.trans_release();
-        let _ = history::record(global, "install", "dry-run", packages, "commit skipped by --test");
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "dry-run", packages, "commit skipped by --test");
         return Ok(());
     }
     
+    let changes = if download_only { Vec::new() } else { transaction_changes(&handle) };
+    let (_, _, total_download, _) = add_summary(&handle);
+    alpm_ops::set_download_cb(&mut handle, global, total_download);
     let commit = handle.trans_commit();
-    let _ = handle.trans_release();
+    trans_end(&mut handle);
     if commit.is_ok() {
-        apply_install_reasons(&handle, packages, global)?;
-        let _ = history::record(global, "install", "success", packages, "transaction committed");
+        if !download_only {
+            apply_install_reasons(&handle, packages, global)?;
+        }
+        let summary = format!("transaction committed{}", group_note);
+        let _ = history::record_changes(global, op, "success", packages, &summary, &changes);
+    } else {
+        let summary = format!("transaction commit failed{}", group_note);
+        let _ = history::record(global, op, "failed", packages, &summary);
+    }
+    commit.map_err(commit_error)
+}
+
+/// When a `<file>.sig` sits next to a local package, require libalpm to verify it
+/// rather than treating the signature as optional. `USE_DEFAULT` is resolved to
+/// the handle's default level first, since libalpm would otherwise ignore the
+/// other bits.
+pub fn detached_sig_level(handle: &alpm::Alpm, siglevel: SigLevel, global: &GlobalFlags) -> SigLevel {
+    if global.insecure_skip_signatures {
+        return siglevel;
+    }
+    let base = if siglevel.contains(SigLevel::USE_DEFAULT) {
+        handle.default_siglevel()
     } else {
-        let _ = history::record(global, "install", "failed", packages, "transaction commit failed");
+        siglevel
+    };
+    let mut level = base | SigLevel::PACKAGE;
+    level.remove(SigLevel::PACKAGE_OPTIONAL);
+    level
+}
+
+fn is_remote_target(target: &str) -> bool {
+    target.starts_with("http://") || target.starts_with("https://")
+}
+
+/// `-U` accepts URLs like pacman: each package is downloaded into the cache,
+/// with its detached signature when the server has one, and installed from
+/// there. A download interrupted by an earlier run resumes from its `.part` file.
+/// Files that a sync database also lists must match its SHA-256.
+fn fetch_remote_packages(handle: &alpm::Alpm, global: &GlobalFlags, pkg_files: &[String]) -> Result<Vec<String>> {
+    if !pkg_files.iter().any(|target| is_remote_target(target)) {
+        return Ok(pkg_files.to_vec());
     }
-    commit.map_err(|e| e.into())
+    let cache_dir = alpm_ops::get_cache_dir(global)?;
+    let mut local = Vec::with_capacity(pkg_files.len());
+    for target in pkg_files {
+        if !is_remote_target(target) {
+            local.push(target.clone());
+            continue;
+        }
+        let path = target.split(['?', '#']).next().unwrap_or(target);
+        let file_name = path.rsplit('/').next().unwrap_or_default();
+        if file_name.is_empty() {
+            bail!("error: '{}' does not name a package file", target);
+        }
+        let dest = Path::new(&cache_dir).join(file_name);
+        if !global.compact {
+            println!(":: {} {}", "Retrieving".cyan().bold(), file_name);
+        }
+        let expected_sha256 = handle
+            .syncdbs()
+            .iter()
+            .flat_map(|db| db.pkgs().iter())
+            .find(|pkg| pkg.filename() == Some(file_name))
+            .and_then(|pkg| pkg.sha256sum());
+        match expected_sha256 {
+            Some(sum) => download::download_file_checked(target, &dest, sum),
+            None => download::download_file(target, &dest),
+        }
+        .map_err(|e| anyhow::anyhow!("error: failed retrieving '{}': {:#}", file_name, e))?;
+        // Most servers publish no signature; pkg_load complains if one was required
+        let sig_url = format!("{}.sig", path);
+        let sig = download::download_file(&sig_url, &dest.with_file_name(format!("{}.sig", file_name)));
+        match sig {
+            Err(err) if global.verbose => println!(":: verbose: no signature for {}: {:#}", file_name, err),
+            _ => {}
+        }
+        local.push(dest.to_string_lossy().into_owned());
+    }
+    Ok(local)
 }
 
 pub fn install_local(global: &GlobalFlags, pkg_files: &[String]) -> Result<()> {
     let mut handle = alpm_ops::init_handle(global)?;
+    let pkg_files = &fetch_remote_packages(&handle, global, pkg_files)?;
     let siglevel = alpm_ops::local_file_siglevel(global)?;
     
     let mut flags = TransFlag::NONE;
+    if global.needed {
+        flags |= TransFlag::NEEDED;
+    }
     if global.nodeps > 0 {
         flags |= TransFlag::NO_DEPS;
     }
@@ -253,57 +878,114 @@ pub fn install_local(global: &GlobalFlags, pkg_files: &[String]) -> Result<()> {
     if global.noscriptlet {
         flags |= TransFlag::NO_SCRIPTLET;
     }
+    if global.dbonly {
+        flags |= TransFlag::DB_ONLY;
+    }
     if global.verbose {
         println!(":: verbose: operation=install-local files={}", pkg_files.join(" "));
     }
     
-    handle.trans_init(flags)?;
+    if global.print_only {
+        flags |= TransFlag::NO_LOCK;
+    }
+    trans_begin(&mut handle, flags)?;
     let mut names: Vec<String> = Vec::new();
     for file in pkg_files {
-        let pkg = handle.pkg_load(file.as_str(), true, siglevel)?;
+        let sig_path = format!("{}.sig", file);
+        let has_detached_sig = Path::new(&sig_path).is_file();
+        let level = if has_detached_sig {
+            detached_sig_level(&handle, siglevel, global)
+        } else {
+            siglevel
+        };
+        if has_detached_sig && global.verbose {
+            println!(":: verbose: verifying {} with detached signature {}", file, sig_path);
+        }
+        let pkg = match handle.pkg_load(file.as_str(), true, level) {
+            Ok(pkg) => pkg,
+            Err(err) => {
+                trans_end(&mut handle);
+                if has_detached_sig && matches!(err, alpm::Error::PkgInvalidSig | alpm::Error::SigInvalid) {
+                    bail!("error: detached signature {} is invalid for {}: {}", sig_path, file, err);
+                }
+                return Err(err.into());
+            }
+        };
+        if global.needed {
+            let up_to_date = handle
+                .localdb()
+                .pkg(pkg.name())
+                .map(|installed| installed.version() == pkg.version())
+                .unwrap_or(false);
+            if up_to_date {
+                println!(
+                    " {} {}-{} is up to date -- skipping, already installed",
+                    "note:".yellow(),
+                    pkg.name(),
+                    pkg.version()
+                );
+                continue;
+            }
+        }
         names.push(pkg.name().to_string());
-        handle
-            .trans_add_pkg(pkg)
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let added = handle.trans_add_pkg(pkg).map_err(add_error);
+        release_on_err(&mut handle, added)?;
     }
     
-    if !global.compact {
+    if !global.compact && !global.print_only {
         println!("{}", "resolving dependencies...".cyan());
         println!("{}", "looking for conflicting packages...".cyan());
     }
     trans_prepare_or_release(&mut handle)?;
+    if global.print_only {
+        print_trans_targets(&mut handle, false);
+        return Ok(());
+    }
     
     let to_install = handle.trans_add();
     if to_install.is_empty() {
-        let _ = handle.trans_release();
+        trans_end(&mut handle);
         println!(" {}", "there is nothing to do".yellow());
         let _ = history::record(global, "install-local", "noop", &names, "no packages to install");
         return Ok(());
     }
     print_add_summary(&handle, global);
+    if global.explain {
+        print_explain(&handle, &names, false);
+    }
     
-    if !global.test && !global.noconfirm && !utils::confirm_action("\n:: Proceed with installation? [Y/n] ") {
-        let _ = handle.trans_release();
+    if global.overwrite_all() && !confirm_overwrite_all(&handle, global) {
+        trans_end(&mut handle);
+        let _ = history::record(global, "install-local", "cancelled", &names, "overwrite-all not confirmed");
+        return Ok(());
+    }
+    
+    if !global.test
+        && !global.noconfirm
+        && !global.pre_answered("install")
+        && !utils::confirm_action("\n:: Proceed with installation? [Y/n] ") {
+        trans_end(&mut handle);
         let _ = history::record(global, "install-local", "cancelled", &names, "user cancelled transaction");
         return Ok(());
     }
     
     if global.test {
         println!(":: {}", "--test: skipping commit".yellow());
-        let _ = handle.trans_release();
+        trans_end(&mut handle);
         let _ = history::record(global, "install-local", "dry-run", &names, "commit skipped by --test");
         return Ok(());
     }
     
+    let changes = transaction_changes(&handle);
     let commit = handle.trans_commit();
-    let _ = handle.trans_release();
+    trans_end(&mut handle);
     if commit.is_ok() {
         apply_install_reasons(&handle, &names, global)?;
-        let _ = history::record(global, "install-local", "success", &names, "transaction committed");
+        let _ = history::record_changes(global, "install-local", "success", &names, "transaction committed", &changes);
     } else {
         let _ = history::record(global, "install-local", "failed", &names, "transaction commit failed");
     }
-    commit.map_err(|e| e.into())
+    commit.map_err(commit_error)
 }
 
 pub fn remove_packages(packages: &[String], remove: &RemoveFlags, global: &GlobalFlags) -> Result<()> {
@@ -311,15 +993,30 @@ pub fn remove_packages(packages: &[String], remove: &RemoveFlags, global: &Globa
     if global.verbose {
         println!(":: verbose: operation=remove targets={}", packages.join(" "));
     }
-    warn_remove_breakage(&handle, packages, remove)?;
+    if global.nodeps > 1 {
+        if !global.print_only && !confirm_nodeps_removal(&handle, packages, global)? {
+            let _ = history::record(global, "remove", "cancelled", packages, "dangling dependencies not confirmed");
+            return Ok(());
+        }
+    } else {
+        warn_remove_breakage(&handle, packages, remove)?;
+    }
     
     let mut flags = TransFlag::NONE;
-    if remove.recursive {
-        flags |= TransFlag::RECURSE | TransFlag::UNNEEDED;
+    match remove.recursive {
+        0 => {}
+        1 => flags |= TransFlag::RECURSE | TransFlag::UNNEEDED,
+        _ => flags |= TransFlag::RECURSE,
+    }
+    if remove.cascade {
+        flags |= TransFlag::CASCADE;
     }
     if remove.nosave {
         flags |= TransFlag::NO_SAVE;
     }
+    if global.dbonly {
+        flags |= TransFlag::DB_ONLY;
+    }
     if global.nodeps > 0 {
         flags |= TransFlag::NO_DEPS;
     }
@@ -327,73 +1024,124 @@ pub fn remove_packages(packages: &[String], remove: &RemoveFlags, global: &Globa
         flags |= TransFlag::NO_DEP_VERSION;
     }
     
-    handle.trans_init(flags)?;
+    if global.print_only {
+        flags |= TransFlag::NO_LOCK;
+    }
+    trans_begin(&mut handle, flags)?;
     for name in packages {
-        let pkg = alpm_ops::find_local_pkg(&handle, name)?;
-        handle.trans_remove_pkg(pkg)?;
+        let removed = alpm_ops::find_local_pkg(&handle, name)
+            .and_then(|pkg| handle.trans_remove_pkg(pkg).map_err(Into::into));
+        release_on_err(&mut handle, removed)?;
     }
     
-    if !global.compact {
+    if !global.compact && !global.print_only {
         println!("{}", "checking dependencies...".cyan());
         println!("{}", "looking for conflicting packages...".cyan());
     }
     trans_prepare_or_release(&mut handle)?;
+    if global.print_only {
+        print_trans_targets(&mut handle, true);
+        return Ok(());
+    }
     
     let to_remove = handle.trans_remove();
     if to_remove.is_empty() {
-        let _ = handle.trans_release();
+        trans_end(&mut handle);
         println!(" {}", "there is nothing to do".yellow());
         let _ = history::record(global, "remove", "noop", packages, "no packages to remove");
         return Ok(());
     }
     print_remove_summary(&handle, global);
+    if remove.recursive > 0 || remove.cascade {
+        print_extra_removals(&handle, packages, remove, global);
+    }
     
-    if !global.test && !global.noconfirm && !utils::confirm_action("\n:: Proceed with removal? [Y/n] ") {
-        let _ = handle.trans_release();
+    if !global.test
+        && !global.noconfirm
+        && !global.pre_answered("remove")
+        && !utils::confirm_action("\n:: Proceed with removal? [Y/n] ") {
+        trans_end(&mut handle);
         let _ = history::record(global, "remove", "cancelled", packages, "user cancelled transaction");
         return Ok(());
     }
     
     if global.test {
         println!(":: {}", "--test: skipping commit".yellow());
-        let _ = handle.trans_release();
+        trans_end(&mut handle);
         let _ = history::record(global, "remove", "dry-run", packages, "commit skipped by --test");
         return Ok(());
     }
     
+    let changes = transaction_changes(&handle);
     let commit = handle.trans_commit();
-    let _ = handle.trans_release();
+    trans_end(&mut handle);
     if commit.is_ok() {
-        let _ = history::record(global, "remove", "success", packages, "transaction committed");
+        let _ = history::record_changes(global, "remove", "success", packages, "transaction committed", &changes);
     } else {
         let _ = history::record(global, "remove", "failed", packages, "transaction commit failed");
     }
-    commit.map_err(|e| e.into())
+    commit.map_err(commit_error)
 }
 
+/// `refresh` counts the `y`s: 1 updates stale databases, 2 (`-Syy`) downloads
+/// them all again.
 pub fn sync_install(
     global: &GlobalFlags,
-    refresh: bool,
+    refresh: u8,
     upgrade: bool,
+    allow_downgrade: bool,
+    download_only: bool,
+    check_news: bool,
     targets: &[String],
 ) -> Result<()> {
     let mut handle = alpm_ops::init_handle(global)?;
+    // Upgrades get their own op so the news check can tell them from plain installs
+    let op = if download_only {
+        "download"
+    } else if upgrade {
+        "upgrade"
+    } else {
+        "sync"
+    };
+    let mut times = PhaseTimes::new(global);
     if global.verbose {
-        println!(":: verbose: operation=sync refresh={} upgrade={} targets={}", refresh, upgrade, targets.join(" "));
+        println!(
+            ":: verbose: operation=sync refresh={} upgrade={} downgrade={} targets={}",
+            refresh,
+            upgrade,
+            allow_downgrade,
+            targets.join(" ")
+        );
     }
     
-    if refresh {
+    if refresh > 0 {
+        let force = refresh > 1;
         if !global.compact {
             println!(":: {}", "Synchronizing package databases...".cyan().bold());
         }
+        if force {
+            println!(
+                ":: {}",
+                "Forcing a refresh: every database is downloaded again, even if up to date".yellow()
+            );
+        }
         if global.test {
             println!(":: {}", "--test: skipping database update".yellow());
         } else {
-            handle.syncdbs_mut().update(false)?;
+            let lockfile = handle.lockfile().to_string();
+            times.measure("refresh", || {
+                interrupt::while_locked(&lockfile, || handle.syncdbs_mut().update(force))
+            })?;
         }
     }
     
     if !upgrade && targets.is_empty() {
+        times.print();
+        return Ok(());
+    }
+    
+    if upgrade && check_news && !download_only && !global.print_only && !news::check_before_upgrade(global)? {
+        let _ = history::record(global, op, "cancelled", targets, "unread news not acknowledged");
         return Ok(());
     }
     
@@ -410,38 +1158,69 @@ pub fn sync_install(
     if global.noscriptlet {
         flags |= TransFlag::NO_SCRIPTLET;
     }
-    handle.trans_init(flags)?;
+    if global.dbonly {
+        flags |= TransFlag::DB_ONLY;
+    }
+    if download_only {
+        flags |= TransFlag::DOWNLOAD_ONLY;
+    }
+    if global.print_only {
+        flags |= TransFlag::NO_LOCK;
+    }
+    trans_begin(&mut handle, flags)?;
     if upgrade {
-        if !global.compact {
+        if !global.compact && !global.print_only {
             println!(":: {}", "Starting full system upgrade...".cyan().bold());
         }
-        handle.sync_sysupgrade(false)?;
-    }
-    for name in targets {
-        let pkg = alpm_ops::find_sync_pkg(&handle, name)?;
-        handle
-            .trans_add_pkg(pkg)
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        if allow_downgrade {
+            eprintln!(
+                "{} {}",
+                "warning:".yellow().bold(),
+                "downgrades are enabled; packages newer than the repository version will be moved backward".yellow()
+            );
+        }
+        let upgraded = handle.sync_sysupgrade(allow_downgrade);
+        release_on_err(&mut handle, upgraded)?;
+        if !global.print_only {
+            print_ignored_upgrades(&handle);
+        }
     }
+    let added = add_sync_targets(&handle, global, targets, !global.print_only);
+    let Some(groups) = release_on_err(&mut handle, added)? else {
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "cancelled", targets, "group installation declined");
+        return Ok(());
+    };
+    let group_note = groups.iter().map(|g| format!("; {}", g)).collect::<String>();
     
-    if !global.compact {
+    if !global.compact && !global.print_only {
         println!("{}", "resolving dependencies...".cyan());
         println!("{}", "looking for conflicting packages...".cyan());
     }
-    trans_prepare_or_release(&mut handle)?;
+    times.measure("resolve", || trans_prepare_or_release(&mut handle))?;
+    if global.print_only {
+        print_trans_targets(&mut handle, false);
+        return Ok(());
+    }
+    print_needed_skips(&handle, targets, global);
     
     let to_add = handle.trans_add();
     if to_add.is_empty() {
-        let _ = handle.trans_release();
+        trans_end(&mut handle);
         println!(" {}", "there is nothing to do".yellow());
-        let _ = history::record(global, "sync", "noop", targets, "no package changes");
+        times.print();
+        let _ = history::record(global, op, "noop", targets, "no package changes");
         return Ok(());
     }
     print_add_summary(&handle, global);
+    if global.explain {
+        print_explain(&handle, targets, upgrade);
+    }
     if !global.compact {
         println!("\n{}", "Packages to upgrade/install:".bold());
     }
     let localdb = handle.localdb();
+    let replaced = replacements(&handle);
     if !global.compact {
         for pkg in to_add.iter() {
             let old_ver = localdb
@@ -451,29 +1230,126 @@ pub fn sync_install(
             println!("  {} {} -> {}", pkg.name(), old_ver, pkg.version());
         }
     }
+    for (old, new) in &replaced {
+        println!(" {} replacing {} with {}", "::".blue().bold(), old.bold(), new.green().bold());
+    }
+    let replaced_note = replaced
+        .iter()
+        .map(|(old, new)| format!("; replaced {} with {}", old, new))
+        .collect::<String>();
+    
+    if allow_downgrade {
+        let downgrades: Vec<String> = to_add
+            .iter()
+            .filter_map(|pkg| {
+                let old = localdb.pkg(pkg.name()).ok()?;
+                if old.version() > pkg.version() {
+                    Some(format!("{} {} -> {}", pkg.name(), old.version(), pkg.version()))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if !downgrades.is_empty() {
+            println!("\n{} ({})", "Packages to downgrade".red().bold(), downgrades.len());
+            for line in &downgrades {
+                println!("  {}", line.red());
+            }
+            if !global.test
+                && !global.noconfirm
+                && !utils::confirm_with_default("\n:: Really downgrade these packages? [y/N] ", false)
+            {
+                trans_end(&mut handle);
+                let _ = history::record(global, op, "cancelled", targets, "user declined downgrades");
+                return Ok(());
+            }
+        }
+    }
     
-    if !global.test && !global.noconfirm && !utils::confirm_action("\n:: Proceed with installation? [Y/n] ") {
-        let _ = handle.trans_release();
-        let _ = history::record(global, "sync", "cancelled", targets, "user cancelled transaction");
+    if global.overwrite_all() && !confirm_overwrite_all(&handle, global) {
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "cancelled", targets, "overwrite-all not confirmed");
+        return Ok(());
+    }
+    
+    if !confirm_download_size(&handle, global) {
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "cancelled", targets, "download size not confirmed");
+        return Ok(());
+    }
+    
+    if !global.test
+        && !global.noconfirm
+        && !global.pre_answered("install")
+        && !utils::confirm_action(proceed_prompt(download_only)) {
+        trans_end(&mut handle);
+        let _ = history::record(global, op, "cancelled", targets, "user cancelled transaction");
         return Ok(());
     }
     
     if global.test {
         println!(":: {}", "--test: skipping commit".yellow());
-        let _ = handle.trans_release();
-        let _ = history::record(global, "sync", "dry-run", targets, "commit skipped by --test");
+        trans_end(&mut handle);
+        times.print();
+        let _ = history::record(global, op, "dry-run", targets, "commit skipped by --test");
         return Ok(());
     }
     
-    let commit = handle.trans_commit();
-    let _ = handle.trans_release();
+    let changes = if download_only { Vec::new() } else { transaction_changes(&handle) };
+    let (_, _, total_download, _) = add_summary(&handle);
+    alpm_ops::set_download_cb(&mut handle, global, total_download);
+    let commit = times.measure("download + commit", || handle.trans_commit());
+    trans_end(&mut handle);
+    times.print();
     if commit.is_ok() {
-        apply_install_reasons(&handle, targets, global)?;
-        let _ = history::record(global, "sync", "success", targets, "transaction committed");
+        if !download_only {
+            apply_install_reasons(&handle, targets, global)?;
+        }
+        let summary = format!("transaction committed{}{}", group_note, replaced_note);
+        let _ = history::record_changes(global, op, "success", targets, &summary, &changes);
     } else {
-        let _ = history::record(global, "sync", "failed", targets, "transaction commit failed");
+        let summary = format!("transaction commit failed{}{}", group_note, replaced_note);
+        let _ = history::record(global, op, "failed", targets, &summary);
+    }
+    commit.map_err(commit_error)
+}
+
+/// `-Sp`: resolves the transaction without taking the database lock and prints
+/// one download URL per package (targets and pulled-in dependencies).
+pub fn print_sync_urls(global: &GlobalFlags, upgrade: bool, targets: &[String]) -> Result<()> {
+    let mut handle = alpm_ops::init_handle(global)?;
+    let mut flags = TransFlag::NO_LOCK;
+    if global.nodeps > 0 {
+        flags |= TransFlag::NO_DEPS;
+    }
+    if global.nodeps > 1 {
+        flags |= TransFlag::NO_DEP_VERSION;
+    }
+    trans_begin(&mut handle, flags)?;
+    if upgrade {
+        let upgraded = handle.sync_sysupgrade(false);
+        release_on_err(&mut handle, upgraded)?;
+    }
+    let added = add_sync_targets(&handle, global, targets, false);
+    release_on_err(&mut handle, added)?;
+    if !global.compact {
+        eprintln!("{}", "resolving dependencies...".cyan());
+    }
+    trans_prepare_or_release(&mut handle)?;
+    
+    for pkg in handle.trans_add().iter() {
+        let filename = pkg.filename().unwrap_or_default();
+        match pkg.db().and_then(|db| db.servers().iter().next()) {
+            Some(server) => println!("{}/{}", server.trim_end_matches('/'), filename),
+            None => eprintln!(
+                "{} no server configured for {}; cannot print its URL",
+                "warning:".yellow().bold(),
+                pkg.name()
+            ),
+        }
     }
-    commit.map_err(|e| e.into())
+    trans_end(&mut handle);
+    Ok(())
 }
 
 pub fn clean_cache(global: &GlobalFlags, level: u8) -> Result<()> {
@@ -486,10 +1362,65 @@ pub fn clean_cache(global: &GlobalFlags, level: u8) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
     let localdb = handle.localdb();
     
+    let candidates = cache_removal_candidates(cache_path, level, |name, version| {
+        localdb.pkg(name).is_ok_and(|pkg| pkg.version().to_string() == version)
+    })?;
+    
+    if candidates.is_empty() {
+        println!(":: {}", "Cache is clean".green().bold());
+        return Ok(());
+    }
+    
+    let reclaimable: i64 = candidates.iter().map(|(_, size)| size).sum();
+    println!(
+        ":: {} {} files, {} can be reclaimed in {}",
+        "Cache:".cyan().bold(),
+        candidates.len(),
+        format_bytes(reclaimable),
+        cache_dir
+    );
+    if global.test {
+        println!(":: {}", "--test: leaving the cache untouched".yellow());
+        return Ok(());
+    }
+    if level >= 2
+        && !global.noconfirm
+        && !utils::confirm_with_default(":: Remove ALL files from the package cache? [y/N] ", false)
+    {
+        println!(" {}", "cache left untouched".yellow());
+        return Ok(());
+    }
+    
     let mut removed = 0usize;
+    let mut freed = 0i64;
+    for (path, size) in &candidates {
+        if fs::remove_file(path).is_ok() {
+            removed += 1;
+            freed += size;
+        }
+    }
+    println!(
+        ":: {} {} files removed, {} freed",
+        "Cache cleaned:".green().bold(),
+        removed,
+        format_bytes(freed)
+    );
+    
+    Ok(())
+}
+
+/// Picks the cache files `-Sc` (level 1) or `-Scc` (level 2) deletes, with their
+/// sizes. A package's `.sig` goes with it, and a `.sig` whose package file is
+/// gone is always removed.
+fn cache_removal_candidates(
+    cache_path: &Path,
+    level: u8,
+    is_installed: impl Fn(&str, &str) -> bool,
+) -> Result<Vec<(PathBuf, i64)>> {
+    let size_of = |path: &Path| fs::metadata(path).map(|m| m.len() as i64).unwrap_or(0);
+    let mut candidates = Vec::new();
     for entry in fs::read_dir(cache_path)? {
-        let entry = entry?;
-        let path = entry.path();
+        let path = entry?.path();
         if !path.is_file() {
             continue;
         }
@@ -501,35 +1432,37 @@ pub fn clean_cache(global: &GlobalFlags, level: u8) -> Result<()> {
             continue;
         }
         
+        if let Some(pkg_file) = file_name.strip_suffix(".sig") {
+            if !cache_path.join(pkg_file).is_file() {
+                candidates.push((path.clone(), size_of(&path)));
+            }
+            continue;
+        }
+        
         let remove = if level >= 2 {
             true
         } else {
             match parse_pkg_filename(file_name) {
-                Some((name, version)) => {
-                    match localdb.pkg(name.as_str()) {
-                        Ok(pkg) => pkg.version().to_string() != version,
-                        Err(_) => true,
-                    }
-                }
+                Some((name, version)) => !is_installed(name.as_str(), version.as_str()),
                 None => false,
             }
         };
         
         if remove {
-            let _ = fs::remove_file(&path);
-            removed += 1;
+            let sig_path = cache_path.join(format!("{}.sig", file_name));
+            candidates.push((path.clone(), size_of(&path)));
+            if sig_path.is_file() {
+                let size = size_of(&sig_path);
+                candidates.push((sig_path, size));
+            }
         }
     }
-    
-    if removed > 0 {
-        println!(":: {} {}", "Cache cleaned:".green().bold(), format!("{} files removed", removed));
-    } else {
-        println!(":: {}", "Cache is clean".green().bold());
-    }
-    
-    Ok(())
+    Ok(candidates)
 }
 
+/// Splits `<name>-[<epoch>:]<ver>-<rel>-<arch>.pkg.tar.*` into the name and the
+/// full version, epoch included so it compares equal to `pkg.version()`.
+/// Files fetched with a percent-encoded colon (`1%3A2.3.4`) are accepted too.
 fn parse_pkg_filename(file_name: &str) -> Option<(String, String)> {
     let base = file_name.split(".pkg.tar").next()?;
     let mut parts = base.rsplitn(4, '-');
@@ -540,10 +1473,189 @@ fn parse_pkg_filename(file_name: &str) -> Option<(String, String)> {
     if arch.is_empty() || rel.is_empty() || ver.is_empty() || name.is_empty() {
         return None;
     }
+    let ver = ver.replace("%3A", ":").replace("%3a", ":");
+    if let Some((epoch, pkgver)) = ver.split_once(':') {
+        if pkgver.is_empty() || epoch.is_empty() || !epoch.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+    }
     let version = format!("{}-{}", ver, rel);
     Some((name.to_string(), version))
 }
 
+/// Finds `<name>-<version>-<arch>.pkg.tar.*` in the package cache.
+fn cached_package_file(cache_dir: &str, name: &str, version: &str) -> Option<String> {
+    fs::read_dir(cache_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            let Some(file_name) = path.file_name().and_then(|s| s.to_str()) else {
+                return false;
+            };
+            !file_name.ends_with(".sig")
+                && parse_pkg_filename(file_name).is_some_and(|(n, v)| n == name && v == version)
+        })
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// `history undo <id>`: reverses a recorded transaction. Packages it installed
+/// are removed; packages it upgraded, downgraded or removed are reinstalled at
+/// their previous version from the package cache.
+pub fn undo_transaction(global: &GlobalFlags, id: &str) -> Result<()> {
+    let Some(entry) = history::find_entry(global, id)? else {
+        bail!("error: history entry not found: {}", id);
+    };
+    // Downloads and `-D` reason changes never ran a package transaction
+    if entry.status != "success" || matches!(entry.op.as_str(), "download" | "database") {
+        bail!("error: entry {} ({} {}) did not change the system; nothing to undo", id, entry.op, entry.status);
+    }
+    if entry.changes.is_empty() {
+        bail!(
+            "error: entry {} has no recorded package versions (it was written by an older rustpack) and cannot be undone",
+            id
+        );
+    }
+    
+    let mut handle = alpm_ops::init_handle(global)?;
+    let cache_dir = alpm_ops::get_cache_dir(global)?;
+    let mut to_remove = Vec::new();
+    let mut to_restore = Vec::new();
+    let mut missing = Vec::new();
+    {
+        let localdb = handle.localdb();
+        for change in &entry.changes {
+            let current = localdb.pkg(change.name.as_str()).ok().map(|pkg| pkg.version().to_string());
+            if current != change.new {
+                bail!(
+                    "error: {} is {} now but the transaction left it at {}; undo would discard later changes",
+                    change.name,
+                    current.as_deref().unwrap_or("not installed"),
+                    change.new.as_deref().unwrap_or("not installed")
+                );
+            }
+            match change.old.as_deref() {
+                None => to_remove.push(change.name.clone()),
+                Some(old) => match cached_package_file(&cache_dir, &change.name, old) {
+                    Some(path) => to_restore.push(path),
+                    None => missing.push(format!("{}-{}", change.name, old)),
+                },
+            }
+        }
+    }
+    if !missing.is_empty() {
+        bail!(
+            "error: cannot undo {}: the cache has no package files for {}\n       place them in {} (e.g. from the Arch Linux Archive) and try again",
+            id,
+            missing.join(" "),
+            cache_dir
+        );
+    }
+    
+    let siglevel = alpm_ops::local_file_siglevel(global)?;
+    trans_begin(&mut handle, TransFlag::NONE)?;
+    for path in &to_restore {
+        let pkg = match handle.pkg_load(path.as_str(), true, siglevel) {
+            Ok(pkg) => pkg,
+            Err(err) => {
+                trans_end(&mut handle);
+                bail!("error: could not load cached package {}: {}", path, err);
+            }
+        };
+        let added = handle.trans_add_pkg(pkg).map_err(add_error);
+        release_on_err(&mut handle, added)?;
+    }
+    for name in &to_remove {
+        let removed = alpm_ops::find_local_pkg(&handle, name)
+            .and_then(|pkg| handle.trans_remove_pkg(pkg).map_err(Into::into));
+        release_on_err(&mut handle, removed)?;
+    }
+    
+    if !global.compact {
+        println!("{}", "resolving dependencies...".cyan());
+        println!("{}", "looking for conflicting packages...".cyan());
+    }
+    trans_prepare_or_release(&mut handle)?;
+    if !handle.trans_add().is_empty() {
+        print_add_summary(&handle, global);
+    }
+    if !handle.trans_remove().is_empty() {
+        print_remove_summary(&handle, global);
+    }
+    
+    let targets = vec![id.to_string()];
+    if !global.test
+        && !global.noconfirm
+        && !utils::confirm_action(format!("\n:: Undo transaction {}? [Y/n] ", id).as_str()) {
+        trans_end(&mut handle);
+        let _ = history::record(global, "undo", "cancelled", &targets, "user cancelled transaction");
+        return Ok(());
+    }
+    
+    if global.test {
+        println!(":: {}", "--test: skipping commit".yellow());
+        trans_end(&mut handle);
+        let _ = history::record(global, "undo", "dry-run", &targets, "commit skipped by --test");
+        return Ok(());
+    }
+    
+    let changes = transaction_changes(&handle);
+    let commit = handle.trans_commit();
+    trans_end(&mut handle);
+    if commit.is_ok() {
+        // Packages restored after a removal come back via -U as explicit; give
+        // back the recorded reason. Older entries lack it, so only their
+        // targets are taken as explicit there.
+        let targets_given: Vec<&str> = entry.targets.split_whitespace().map(alpm_ops::target_pkg_name).collect();
+        let localdb = handle.localdb();
+        for change in entry.changes.iter().filter(|c| c.new.is_none()) {
+            let explicit = change.explicit.unwrap_or_else(|| targets_given.contains(&change.name.as_str()));
+            if explicit {
+                continue;
+            }
+            if let Ok(pkg) = localdb.pkg(change.name.as_str()) {
+                let _ = pkg.set_reason(alpm::PackageReason::Depend);
+            }
+        }
+        let summary = format!("reverted {} ({})", id, entry.op);
+        let _ = history::record_changes(global, "undo", "success", &targets, &summary, &changes);
+    } else {
+        let _ = history::record(global, "undo", "failed", &targets, "transaction commit failed");
+    }
+    commit.map_err(commit_error)
+}
+
+/// `-D --asdeps/--asexplicit`: changes the install reason of installed packages
+/// in place, without a transaction.
+pub fn change_install_reasons(global: &GlobalFlags, packages: &[String]) -> Result<()> {
+    let (reason, label) = if global.asdeps {
+        (alpm::PackageReason::Depend, "installed as a dependency for another package")
+    } else {
+        (alpm::PackageReason::Explicit, "explicitly installed")
+    };
+    let handle = alpm_ops::init_handle(global)?;
+    let mut pkgs = Vec::new();
+    for name in packages {
+        pkgs.push(alpm_ops::find_local_pkg(&handle, name)?);
+    }
+    
+    if global.test {
+        for pkg in &pkgs {
+            println!("{}: reason would change to {}", pkg.name(), label);
+        }
+        return Ok(());
+    }
+    for pkg in pkgs {
+        pkg.set_reason(reason)
+            .map_err(|e| anyhow::anyhow!("error: could not set install reason for {}: {}", pkg.name(), e))?;
+        println!("{}: install reason has been set to '{}'", pkg.name(), label);
+    }
+    let _ = history::record(global, "database", "success", packages, &format!("install reason set to {}", label));
+    Ok(())
+}
+
+/// `--asdeps`/`--asexplicit` cover every package a target brought in, so a
+/// group target marks each of its installed members.
 fn apply_install_reasons(handle: &alpm::Alpm, targets: &[String], global: &GlobalFlags) -> Result<()> {
     if !global.asdeps && !global.asexplicit {
         return Ok(());
@@ -555,9 +1667,93 @@ fn apply_install_reasons(handle: &alpm::Alpm, targets: &[String], global: &Globa
     };
     let localdb = handle.localdb();
     for name in targets {
-        if let Ok(pkg) = localdb.pkg(name.as_str()) {
+        if let Ok(pkg) = localdb.pkg(alpm_ops::target_pkg_name(name)) {
             let _ = pkg.set_reason(reason);
+            continue;
+        }
+        for member in alpm_ops::find_sync_group(handle, name).unwrap_or_default() {
+            if let Ok(pkg) = localdb.pkg(member.name()) {
+                let _ = pkg.set_reason(reason);
+            }
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_pkg_filename_keeps_epoch() {
+        assert_eq!(
+            parse_pkg_filename("foo-1:2.3.4-5-x86_64.pkg.tar.zst"),
+            Some(("foo".to_string(), "1:2.3.4-5".to_string()))
+        );
+        assert_eq!(
+            parse_pkg_filename("foo-1%3A2.3.4-5-x86_64.pkg.tar.zst.sig"),
+            Some(("foo".to_string(), "1:2.3.4-5".to_string()))
+        );
+        assert_eq!(
+            parse_pkg_filename("lib32-foo-bar-2.3.4-5.1-any.pkg.tar.xz"),
+            Some(("lib32-foo-bar".to_string(), "2.3.4-5.1".to_string()))
+        );
+        assert_eq!(parse_pkg_filename("foo-x:2.3.4-5-x86_64.pkg.tar.zst"), None);
+        assert_eq!(parse_pkg_filename("foo-x86_64.pkg.tar.zst"), None);
+    }
+    
+    #[test]
+    fn test_cache_removal_takes_signatures_along() {
+        let dir = std::env::temp_dir().join(format!("rustpack-cache-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in [
+            "foo-1-1-x86_64.pkg.tar.zst",
+            "foo-1-1-x86_64.pkg.tar.zst.sig",
+            "bar-2-1-x86_64.pkg.tar.zst",
+            "bar-2-1-x86_64.pkg.tar.zst.sig",
+            "baz-1-1-any.pkg.tar.zst.sig",
+        ] {
+            fs::write(dir.join(file), b"x").unwrap();
+        }
+        let installed = |name: &str, version: &str| name == "bar" && version == "2-1";
+        let names = |level| {
+            let mut names: Vec<String> = cache_removal_candidates(&dir, level, installed)
+                .unwrap()
+                .into_iter()
+                .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        
+        assert_eq!(
+            names(1),
+            vec![
+                "baz-1-1-any.pkg.tar.zst.sig",
+                "foo-1-1-x86_64.pkg.tar.zst",
+                "foo-1-1-x86_64.pkg.tar.zst.sig",
+            ]
+        );
+        assert_eq!(names(2).len(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_explain_roots_cover_groups_and_sysupgrade() {
+        let names = ["gnome-shell", "mutter", "glib2", "linux", "firefox", "nss"];
+        let groups = |group: &str| (group == "gnome").then(|| vec!["gnome-shell".to_string(), "mutter".to_string()]);
+        let installed = |name: &str| name == "linux" || name == "glib2";
+        
+        // Group members are the roots; their dependencies are not
+        let roots = explain_roots(&names, &["gnome".to_string()], false, groups, installed);
+        assert_eq!(roots, vec!["gnome-shell", "mutter"]);
+        
+        // -Syu firefox: the target plus every installed package being upgraded
+        let roots = explain_roots(&names, &["extra/firefox".to_string()], true, groups, installed);
+        assert_eq!(roots, vec!["glib2", "linux", "firefox"]);
+        
+        // -Syu alone
+        let roots = explain_roots(&names, &[], true, groups, installed);
+        assert_eq!(roots, vec!["glib2", "linux"]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+
+/// No transaction, or one started with `NO_LOCK`.
+const IDLE: u8 = 0;
+/// A transaction holds `db.lck` but nothing has been committed yet.
+const LOCKED: u8 = 1;
+/// libalpm has started changing the system; stopping now could leave it half-written.
+const COMMITTING: u8 = 2;
+
+static PHASE: AtomicU8 = AtomicU8::new(IDLE);
+static LOCK_PATH: AtomicPtr<c_char> = AtomicPtr::new(ptr::null_mut());
+
+/// Installs the SIGINT handler. Ctrl-C before packages are installed or removed
+/// (downloads included) removes the lock the transaction took and exits with
+/// 130; after that it is ignored so libalpm can finish writing the database.
+pub fn install_handler() {
+    unsafe {
+        libc::signal(libc::SIGINT, on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+/// Records that a transaction now holds `lockfile`.
+pub fn lock_taken(lockfile: &str) {
+    if let Ok(path) = CString::new(lockfile) {
+        // Leaked on purpose, old paths included: the handler may read them at any time
+        LOCK_PATH.store(path.into_raw(), Ordering::SeqCst);
+    }
+    PHASE.store(LOCKED, Ordering::SeqCst);
+}
+
+/// Runs `f`, which takes and releases `lockfile` itself (a database refresh),
+/// with the handler knowing about the lock. A lock file that already exists
+/// belongs to another process and is left alone.
+pub fn while_locked<T>(lockfile: &str, f: impl FnOnce() -> T) -> T {
+    if Path::new(lockfile).exists() {
+        return f();
+    }
+    lock_taken(lockfile);
+    let result = f();
+    lock_released();
+    result
+}
+
+/// Called once libalpm starts the transaction proper, after the downloads.
+pub fn committing() {
+    if PHASE.load(Ordering::SeqCst) == LOCKED {
+        PHASE.store(COMMITTING, Ordering::SeqCst);
+    }
+}
+
+/// The transaction was released; the lock is no longer ours to remove.
+pub fn lock_released() {
+    PHASE.store(IDLE, Ordering::SeqCst);
+}
+
+// Only async-signal-safe calls in here: write, unlink and _exit.
+extern "C" fn on_sigint(_: libc::c_int) {
+    match PHASE.load(Ordering::SeqCst) {
+        COMMITTING => {
+            write_stderr(b"\ninterrupt ignored: the transaction is being committed, please wait\n");
+            return;
+        }
+        LOCKED => {
+            let path = LOCK_PATH.load(Ordering::SeqCst);
+            if !path.is_null() {
+                unsafe {
+                    libc::unlink(path);
+                }
+            }
+            write_stderr(b"\ninterrupted: transaction cancelled and database lock released\n");
+        }
+        _ => write_stderr(b"\ninterrupted\n"),
+    }
+    unsafe { libc::_exit(130) }
+}
+
+fn write_stderr(msg: &[u8]) {
+    unsafe {
+        libc::write(libc::STDERR_FILENO, msg.as_ptr() as *const libc::c_void, msg.len());
+    }
+}
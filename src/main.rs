@@ -3,14 +3,24 @@ mod install;
 mod search;
 mod utils;
 mod alpm_ops;
+mod aur;
 mod cli;
 mod doctor;
+mod download;
+mod error;
+mod files;
 mod history;
+mod interrupt;
+mod manifest;
+mod mirrors;
+mod news;
+mod verify;
 
 use anyhow::Result;
 use colored::Colorize;
 use std::env;
-use crate::cli::{GlobalFlags, RemoveFlags};
+use crate::cli::{ColorMode, DoctorFlags, GlobalFlags, HistoryFlags, MirrorFlags, RemoveFlags};
+use crate::error::RustpackError;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Operation {
@@ -21,27 +31,62 @@ enum Operation {
     Why,
     Doctor,
     History,
+    Repos,
+    Verify,
+    ConfigTest,
+    Export,
+    Import,
+    Database,
+    Files,
+    CleanOrphans,
+    RankMirrors,
     Help,
 }
 
 #[derive(Default)]
 struct SyncFlags {
-    refresh: bool,
+    refresh: u8,
     upgrade: bool,
+    downgrade: bool,
     search: bool,
-    info: bool,
+    info: u8,
+    list: bool,
+    download_only: bool,
+    print_urls: bool,
+    groups: bool,
     clean_cache: u8,
+    aur_search: bool,
+    no_news: bool,
 }
 
 #[derive(Default)]
 struct QueryFlags {
-    info: bool,
+    info: u8,
     search: bool,
     list_files: bool,
+    missing: bool,
     manual: bool,
+    native: bool,
     owns: bool,
     explicit: bool,
-    reverse_deps: bool,
+    reverse_deps: u8,
+    upgrades: bool,
+    orphans: bool,
+    groups: bool,
+    check: u8,
+    changelog: bool,
+    dot: bool,
+    file: bool,
+}
+
+#[derive(Default)]
+struct DatabaseFlags {
+    check: bool,
+}
+
+#[derive(Default)]
+struct FilesFlags {
+    refresh: u8,
 }
 
 struct ParsedArgs {
@@ -49,6 +94,11 @@ struct ParsedArgs {
     sync: SyncFlags,
     query: QueryFlags,
     remove: RemoveFlags,
+    database: DatabaseFlags,
+    files: FilesFlags,
+    doctor: DoctorFlags,
+    history: HistoryFlags,
+    mirrors: MirrorFlags,
     targets: Vec<String>,
     global: GlobalFlags,
 }
@@ -91,7 +141,15 @@ fn main() -> Result<()> {
             std::process::exit(1);
         }
     };
+    apply_color_mode(parsed.global.color);
     emit_safety_warnings(&parsed.global);
+    interrupt::install_handler();
+    if let Some(retries) = parsed.global.retries {
+        download::set_retries(retries);
+    }
+    if let Some(timeout) = parsed.global.timeout {
+        download::set_timeout(timeout);
+    }
     
     let run_result = match parsed.op {
         Operation::Sync => handle_sync(&parsed),
@@ -101,6 +159,15 @@ fn main() -> Result<()> {
         Operation::Why => handle_why(&parsed),
         Operation::Doctor => handle_doctor(&parsed),
         Operation::History => handle_history(&parsed),
+        Operation::Repos => search::list_repos(&parsed.global),
+        Operation::Verify => verify::verify_files(&parsed.global, &parsed.targets),
+        Operation::ConfigTest => doctor::config_test(&parsed.global),
+        Operation::Export => manifest::export(&parsed.global),
+        Operation::Import => handle_import(&parsed),
+        Operation::Database => handle_database(&parsed),
+        Operation::Files => handle_files(&parsed),
+        Operation::CleanOrphans => handle_clean_orphans(&parsed),
+        Operation::RankMirrors => mirrors::rank(&parsed.global, &parsed.mirrors),
         Operation::Help => {
             print_usage();
             Ok(())
@@ -114,6 +181,32 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// `--sysroot <dir>`: root, database, cache and pacman.conf all live under
+/// `<dir>`, except where `--root`/`--dbpath`/`--cachedir`/`--config` say otherwise.
+/// Paths named inside pacman.conf are rebased when the config is read.
+fn apply_sysroot(global: &mut GlobalFlags, dir: &str) {
+    let root = if dir.is_empty() { "/" } else { dir };
+    if !dir.is_empty() {
+        global.sysroot = Some(dir.to_string());
+    }
+    global.root_dir.get_or_insert_with(|| root.to_string());
+    global.db_path.get_or_insert_with(|| format!("{}/var/lib/pacman", dir));
+    global.cache_dir.get_or_insert_with(|| format!("{}/var/cache/pacman/pkg", dir));
+    global.config_path.get_or_insert_with(|| format!("{}/etc/pacman.conf", dir));
+}
+
+fn apply_color_mode(mode: ColorMode) {
+    let enabled = match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            !no_color && utils::stdout_is_terminal()
+        }
+    };
+    colored::control::set_override(enabled);
+}
+
 fn set_operation(op: &mut Option<Operation>, new_op: Operation) -> std::result::Result<(), String> {
     if let Some(existing) = op {
         if *existing != new_op {
@@ -125,12 +218,39 @@ fn set_operation(op: &mut Option<Operation>, new_op: Operation) -> std::result::
     Ok(())
 }
 
+/// Value of a `--flag=value` or `--flag value` option; the second form
+/// consumes the next argument.
+fn take_value(
+    value_opt: Option<String>,
+    args: &[String],
+    i: &mut usize,
+    flag: &str,
+) -> std::result::Result<String, String> {
+    if let Some(value) = value_opt {
+        return Ok(value);
+    }
+    if *i + 1 < args.len() {
+        *i += 1;
+        return Ok(args[*i].to_string());
+    }
+    Err(format!("error: {} requires a value", flag))
+}
+
 fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
     let mut op: Option<Operation> = None;
     let mut flag_chars: Vec<char> = Vec::new();
     let mut targets: Vec<String> = Vec::new();
     let mut in_options = true;
     let mut global = GlobalFlags::default();
+    let mut doctor = DoctorFlags::default();
+    let mut history = HistoryFlags::default();
+    let mut mirrors = MirrorFlags::default();
+    let mut downgrade = false;
+    let mut aur_search = false;
+    let mut no_news = false;
+    let mut missing = false;
+    let mut dot = false;
+    let mut sysroot: Option<String> = None;
     let mut i = 1;
     
     while i < args.len() {
@@ -167,17 +287,62 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
             i += 1;
             continue;
         }
+        if i == 1 && arg == "why" {
+            set_operation(&mut op, Operation::Why)?;
+            i += 1;
+            continue;
+        }
         if i == 1 && arg == "history" {
             set_operation(&mut op, Operation::History)?;
             i += 1;
             continue;
         }
+        if i == 1 && arg == "export" {
+            set_operation(&mut op, Operation::Export)?;
+            i += 1;
+            continue;
+        }
+        if i == 1 && arg == "import" {
+            set_operation(&mut op, Operation::Import)?;
+            i += 1;
+            continue;
+        }
+        if i == 1 && arg == "verify" {
+            set_operation(&mut op, Operation::Verify)?;
+            i += 1;
+            continue;
+        }
+        if i == 1 && arg == "rankmirrors" {
+            set_operation(&mut op, Operation::RankMirrors)?;
+            i += 1;
+            continue;
+        }
+        if in_options && arg == "--clean-orphans" {
+            set_operation(&mut op, Operation::CleanOrphans)?;
+            i += 1;
+            continue;
+        }
+        if in_options && arg == "--config-test" {
+            set_operation(&mut op, Operation::ConfigTest)?;
+            i += 1;
+            continue;
+        }
+        if (i == 1 && arg == "repos") || (in_options && arg == "--list-repos") {
+            set_operation(&mut op, Operation::Repos)?;
+            i += 1;
+            continue;
+        }
         if in_options && (arg == "-h" || arg == "--help") {
             return Ok(ParsedArgs {
                 op: Operation::Help,
                 sync: SyncFlags::default(),
                 query: QueryFlags::default(),
                 remove: RemoveFlags::default(),
+                database: DatabaseFlags::default(),
+                files: FilesFlags::default(),
+                doctor: DoctorFlags::default(),
+                history: HistoryFlags::default(),
+                mirrors: MirrorFlags::default(),
                 targets: Vec::new(),
                 global: GlobalFlags::default(),
             });
@@ -202,58 +367,166 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
                 "--needed" => global.needed = true,
                 "--nodeps" => global.nodeps = global.nodeps.saturating_add(1),
                 "--noscriptlet" => global.noscriptlet = true,
+                "--dbonly" => global.dbonly = true,
+                "--disable-download-timeout" => global.disable_dl_timeout = true,
                 "--asdeps" => global.asdeps = true,
                 "--asexplicit" => global.asexplicit = true,
                 "--overwrite" => {
-                    let value = value_opt.or_else(|| {
-                        if i + 1 < args.len() {
-                            i += 1;
-                            Some(args[i].to_string())
-                        } else {
-                            None
-                        }
-                    });
-                    let value = value.ok_or_else(|| "error: --overwrite requires a value".to_string())?;
+                    let value = take_value(value_opt, args, &mut i, "--overwrite")?;
                     global.overwrite.push(value);
                 }
-                "--root" => {
-                    let value = value_opt.or_else(|| {
-                        if i + 1 < args.len() {
-                            i += 1;
-                            Some(args[i].to_string())
-                        } else {
-                            None
-                        }
-                    });
-                    global.root_dir = Some(value.ok_or_else(|| "error: --root requires a value".to_string())?);
-                }
-                "--dbpath" => {
-                    let value = value_opt.or_else(|| {
-                        if i + 1 < args.len() {
-                            i += 1;
-                            Some(args[i].to_string())
-                        } else {
-                            None
+                "--ignore" => {
+                    let value = take_value(value_opt, args, &mut i, "--ignore")?;
+                    global.ignore.extend(
+                        value
+                            .split(',')
+                            .map(|name| name.trim())
+                            .filter(|name| !name.is_empty())
+                            .map(|name| name.to_string()),
+                    );
+                }
+                "--root" => global.root_dir = Some(take_value(value_opt, args, &mut i, "--root")?),
+                "--dbpath" => global.db_path = Some(take_value(value_opt, args, &mut i, "--dbpath")?),
+                "--cachedir" => global.cache_dir = Some(take_value(value_opt, args, &mut i, "--cachedir")?),
+                "--logfile" => global.log_file = Some(take_value(value_opt, args, &mut i, "--logfile")?),
+                "--arch" => {
+                    let value = take_value(value_opt, args, &mut i, "--arch")?;
+                    if value.is_empty() || value.contains(char::is_whitespace) {
+                        return Err(format!("error: invalid --arch '{}'", value));
+                    }
+                    global.arch = Some(value);
+                }
+                "--gpgdir" => global.gpg_dir = Some(take_value(value_opt, args, &mut i, "--gpgdir")?),
+                "--sysroot" => sysroot = Some(take_value(value_opt, args, &mut i, "--sysroot")?),
+                "--assume-installed" => {
+                    let value = take_value(value_opt, args, &mut i, "--assume-installed")?;
+                    if !utils::valid_assume_installed(&value) {
+                        return Err(format!(
+                            "error: invalid --assume-installed '{}' (expected name or name=version)",
+                            value
+                        ));
+                    }
+                    global.assume_installed.push(value);
+                }
+                "--config" => global.config_path = Some(take_value(value_opt, args, &mut i, "--config")?),
+                "--yes-to" => {
+                    let value = take_value(value_opt, args, &mut i, "--yes-to")?;
+                    for kind in value.split(',').map(|k| k.trim()).filter(|k| !k.is_empty()) {
+                        if !cli::YES_TO_KINDS.contains(&kind) {
+                            return Err(format!(
+                                "error: invalid --yes-to kind '{}' (expected one of: {})",
+                                kind,
+                                cli::YES_TO_KINDS.join(", ")
+                            ));
                         }
-                    });
-                    global.db_path = Some(value.ok_or_else(|| "error: --dbpath requires a value".to_string())?);
-                }
-                "--cachedir" => {
-                    let value = value_opt.or_else(|| {
-                        if i + 1 < args.len() {
-                            i += 1;
-                            Some(args[i].to_string())
-                        } else {
-                            None
+                        if !global.yes_to.iter().any(|k| k == kind) {
+                            global.yes_to.push(kind.to_string());
                         }
-                    });
-                    global.cache_dir = Some(value.ok_or_else(|| "error: --cachedir requires a value".to_string())?);
+                    }
+                }
+                "--explain" => global.explain = true,
+                "--download-warn" => {
+                    let value = take_value(value_opt, args, &mut i, "--download-warn")?;
+                    let size = utils::parse_size(&value)
+                        .ok_or_else(|| format!("error: invalid --download-warn size '{}' (use e.g. 500M or 2G)", value))?;
+                    global.download_warn = Some(size);
+                }
+                "--downgrade" | "--downgrades" => downgrade = true,
+                "--aur-search" => aur_search = true,
+                "--no-news" => no_news = true,
+                "--timeout" => {
+                    let value = take_value(value_opt, args, &mut i, "--timeout")?;
+                    let timeout = value
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|secs| *secs > 0)
+                        .ok_or_else(|| format!("error: invalid --timeout '{}' (expected seconds, at least 1)", value))?;
+                    global.timeout = Some(timeout);
+                }
+                "--samples" => {
+                    let value = take_value(value_opt, args, &mut i, "--samples")?;
+                    let samples = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("error: invalid --samples '{}' (expected a number, at least 1)", value))?;
+                    mirrors.samples = Some(samples);
+                }
+                "--missing" => missing = true,
+                "--dot" => dot = true,
+                "--check" => doctor.checks.push(take_value(value_opt, args, &mut i, "--check")?),
+                "--list-checks" => doctor.list_checks = true,
+                "--check-mirrors" => doctor.check_mirrors = true,
+                "--older-than" => history.older_than = Some(take_value(value_opt, args, &mut i, "--older-than")?),
+                "--keep" => {
+                    let value = take_value(value_opt, args, &mut i, "--keep")?;
+                    let keep = value
+                        .parse::<usize>()
+                        .map_err(|_| format!("error: invalid --keep '{}' (expected a number of entries)", value))?;
+                    history.keep = Some(keep);
                 }
+                "--op" => history.op = Some(take_value(value_opt, args, &mut i, "--op")?),
+                "--since" => history.since = Some(take_value(value_opt, args, &mut i, "--since")?),
+                "--raw" => history.raw = true,
                 "--strict" => global.strict = true,
                 "--insecure-skip-signatures" => global.insecure_skip_signatures = true,
                 "--json" => global.json = true,
                 "--compact" => global.compact = true,
+                "--no-truncate" => global.no_truncate = true,
+                "--noprogress" | "--noprogressbar" => global.noprogress = true,
+                "--time" => global.time = true,
+                "--print" => global.print_only = true,
+                "--parallel" => {
+                    let value = take_value(value_opt, args, &mut i, "--parallel")?;
+                    let n = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("error: invalid --parallel '{}' (expected a positive number)", value))?;
+                    global.parallel = Some(n);
+                }
+                "--retries" => {
+                    let value = take_value(value_opt, args, &mut i, "--retries")?;
+                    let retries = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|n| *n <= download::MAX_RETRIES)
+                        .ok_or_else(|| {
+                            format!("error: invalid --retries '{}' (expected 0 to {})", value, download::MAX_RETRIES)
+                        })?;
+                    global.retries = Some(retries);
+                }
+                "--width" => {
+                    let value = take_value(value_opt, args, &mut i, "--width")?;
+                    let width = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|w| *w > 0)
+                        .ok_or_else(|| format!("error: invalid --width '{}' (expected a positive column count)", value))?;
+                    global.width = Some(width);
+                }
+                "--limit" => {
+                    let value = take_value(value_opt, args, &mut i, "--limit")?;
+                    let limit = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("error: invalid --limit '{}' (expected a positive number)", value))?;
+                    global.limit = Some(limit);
+                }
+                "--name-only" => global.name_only = true,
+                "--exact" => global.exact = true,
                 "--verbose" => global.verbose = true,
+                "--quiet" => global.quiet = true,
+                "--color" => {
+                    let value = take_value(value_opt, args, &mut i, "--color")?;
+                    global.color = match value.as_str() {
+                        "auto" => ColorMode::Auto,
+                        "always" => ColorMode::Always,
+                        "never" => ColorMode::Never,
+                        _ => return Err(format!("error: invalid --color '{}' (expected auto, always or never)", value)),
+                    };
+                }
                 _ => return Err(format!("error: invalid option '{}'", arg)),
             }
             i += 1;
@@ -267,6 +540,8 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
                     'Q' => set_operation(&mut op, Operation::Query)?,
                     'R' => set_operation(&mut op, Operation::Remove)?,
                     'U' => set_operation(&mut op, Operation::Upgrade)?,
+                    'D' => set_operation(&mut op, Operation::Database)?,
+                    'F' => set_operation(&mut op, Operation::Files)?,
                     _ => flag_chars.push(ch),
                 }
             }
@@ -279,11 +554,34 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
     }
     
     let op = op.ok_or_else(|| "error: no operation specified (use -h for help)".to_string())?;
+    if let Some(dir) = sysroot {
+        apply_sysroot(&mut global, dir.trim_end_matches('/'));
+    }
+    if downgrade && op != Operation::Sync {
+        return Err("error: --downgrade only applies to -Su".to_string());
+    }
+    if aur_search && op != Operation::Sync {
+        return Err("error: --aur-search only applies to -Ss".to_string());
+    }
+    if no_news && op != Operation::Sync {
+        return Err("error: --no-news only applies to -Su".to_string());
+    }
+    if missing && op != Operation::Query {
+        return Err("error: --missing only applies to -Ql".to_string());
+    }
+    if dot && op != Operation::Query {
+        return Err("error: --dot only applies to -Q".to_string());
+    }
     let mut parsed = ParsedArgs {
         op,
         sync: SyncFlags::default(),
         query: QueryFlags::default(),
         remove: RemoveFlags::default(),
+        database: DatabaseFlags::default(),
+        files: FilesFlags::default(),
+        doctor,
+        history,
+        mirrors,
         targets,
         global,
     };
@@ -292,32 +590,112 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
         Operation::Sync => {
             for ch in flag_chars {
                 match ch {
-                    'y' => parsed.sync.refresh = true,
-                    'u' => parsed.sync.upgrade = true,
+                    'y' => parsed.sync.refresh = parsed.sync.refresh.saturating_add(1),
+                    'u' => {
+                        if parsed.sync.upgrade {
+                            parsed.sync.downgrade = true;
+                        }
+                        parsed.sync.upgrade = true;
+                    }
                     's' => parsed.sync.search = true,
-                    'i' => parsed.sync.info = true,
+                    'i' => parsed.sync.info = parsed.sync.info.saturating_add(1),
+                    'l' => parsed.sync.list = true,
+                    'w' => parsed.sync.download_only = true,
+                    'q' => parsed.global.quiet = true,
+                    'p' => parsed.sync.print_urls = true,
+                    'g' => parsed.sync.groups = true,
                     'd' => parsed.global.nodeps = parsed.global.nodeps.saturating_add(1),
                     'c' => parsed.sync.clean_cache = parsed.sync.clean_cache.saturating_add(1),
                     _ => return Err(format!("error: invalid option '-{}' for -S", ch)),
                 }
             }
             
-            if parsed.sync.search && parsed.sync.info {
+            if parsed.sync.search && parsed.sync.info > 0 {
                 return Err("error: only one of -s or -i can be used with -S".to_string());
             }
             
-            if (parsed.sync.search || parsed.sync.info) && (parsed.sync.refresh || parsed.sync.upgrade) {
+            if parsed.sync.groups {
+                if parsed.sync.search
+                    || parsed.sync.info > 0
+                    || parsed.sync.list
+                    || parsed.sync.refresh > 0
+                    || parsed.sync.upgrade
+                    || parsed.sync.download_only
+                    || parsed.sync.print_urls
+                    || parsed.sync.clean_cache > 0
+                {
+                    return Err("error: -Sg cannot be combined with other -S options".to_string());
+                }
+                if parsed.targets.is_empty() {
+                    return Err("error: -Sg requires a group name".to_string());
+                }
+            }
+            
+            if downgrade {
+                parsed.sync.downgrade = true;
+            }
+            if aur_search {
+                if !parsed.sync.search {
+                    return Err("error: --aur-search only applies to -Ss".to_string());
+                }
+                parsed.sync.aur_search = true;
+            }
+            if no_news {
+                if !parsed.sync.upgrade {
+                    return Err("error: --no-news only applies to -Su".to_string());
+                }
+                parsed.sync.no_news = true;
+            }
+            if parsed.sync.downgrade && !parsed.sync.upgrade {
+                return Err("error: --downgrade requires -u (use -Suu or -Su --downgrade)".to_string());
+            }
+            
+            if parsed.sync.list
+                && (parsed.sync.search
+                    || parsed.sync.info > 0
+                    || parsed.sync.refresh > 0
+                    || parsed.sync.upgrade
+                    || parsed.sync.clean_cache > 0)
+            {
+                return Err("error: -Sl cannot be combined with other -S options".to_string());
+            }
+            
+            if parsed.sync.download_only
+                && (parsed.sync.search || parsed.sync.info > 0 || parsed.sync.list || parsed.sync.clean_cache > 0)
+            {
+                return Err("error: -w cannot be combined with -s/-i/-l/-c".to_string());
+            }
+            if parsed.sync.download_only && parsed.global.dbonly {
+                return Err("error: --dbonly cannot be combined with -w".to_string());
+            }
+            
+            if parsed.sync.print_urls
+                && (parsed.sync.search
+                    || parsed.sync.info > 0
+                    || parsed.sync.list
+                    || parsed.sync.refresh > 0
+                    || parsed.sync.download_only
+                    || parsed.sync.clean_cache > 0)
+            {
+                return Err("error: -p can only be combined with -u for -S".to_string());
+            }
+            if parsed.sync.print_urls && !parsed.sync.upgrade && parsed.targets.is_empty() {
+                return Err("error: no targets specified (use -h for help)".to_string());
+            }
+            
+            if (parsed.sync.search || parsed.sync.info > 0) && (parsed.sync.refresh > 0 || parsed.sync.upgrade) {
                 return Err("error: -s/-i cannot be combined with -y/-u".to_string());
             }
             
-            if (parsed.sync.search || parsed.sync.info) && parsed.targets.is_empty() {
+            if (parsed.sync.search || parsed.sync.info > 0) && parsed.targets.is_empty() {
                 return Err("error: no targets specified (use -h for help)".to_string());
             }
             
             if !parsed.sync.search
-                && !parsed.sync.info
+                && parsed.sync.info == 0
+                && !parsed.sync.list
                 && parsed.targets.is_empty()
-                && !parsed.sync.refresh
+                && parsed.sync.refresh == 0
                 && !parsed.sync.upgrade
                 && parsed.sync.clean_cache == 0
             {
@@ -326,8 +704,8 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
             
             if parsed.sync.clean_cache > 0 {
                 if parsed.sync.search
-                    || parsed.sync.info
-                    || parsed.sync.refresh
+                    || parsed.sync.info > 0
+                    || parsed.sync.refresh > 0
                     || parsed.sync.upgrade
                     || !parsed.targets.is_empty()
                 {
@@ -340,21 +718,36 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
             }
         }
         Operation::Query => {
+            let mut deps_only = false;
+            let mut unrequired = false;
             for ch in flag_chars {
                 match ch {
-                    'i' => parsed.query.info = true,
+                    'i' => parsed.query.info = parsed.query.info.saturating_add(1),
                     's' => parsed.query.search = true,
                     'l' => parsed.query.list_files = true,
                     'm' => parsed.query.manual = true,
+                    'n' => parsed.query.native = true,
                     'o' => parsed.query.owns = true,
                     'e' => parsed.query.explicit = true,
-                    'r' => parsed.query.reverse_deps = true,
+                    'r' => parsed.query.reverse_deps = parsed.query.reverse_deps.saturating_add(1),
+                    'u' => parsed.query.upgrades = true,
+                    'g' => parsed.query.groups = true,
+                    'c' => parsed.query.changelog = true,
+                    'p' => parsed.query.file = true,
+                    'k' => parsed.query.check = parsed.query.check.saturating_add(1),
+                    'q' => parsed.global.quiet = true,
+                    'd' => deps_only = true,
+                    't' => unrequired = true,
                     _ => return Err(format!("error: invalid option '-{}' for -Q", ch)),
                 }
             }
+            if deps_only != unrequired {
+                return Err("error: -d and -t must be used together with -Q (use -Qdt)".to_string());
+            }
+            parsed.query.orphans = deps_only && unrequired;
             
             let mut option_count = 0;
-            if parsed.query.info {
+            if parsed.query.info > 0 {
                 option_count += 1;
             }
             if parsed.query.search {
@@ -366,39 +759,91 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
             if parsed.query.manual {
                 option_count += 1;
             }
+            if parsed.query.native {
+                option_count += 1;
+            }
             if parsed.query.owns {
                 option_count += 1;
             }
             if parsed.query.explicit {
                 option_count += 1;
             }
-            if parsed.query.reverse_deps {
+            if parsed.query.reverse_deps > 0 {
+                option_count += 1;
+            }
+            if parsed.query.upgrades {
+                option_count += 1;
+            }
+            if parsed.query.orphans {
+                option_count += 1;
+            }
+            if parsed.query.groups {
+                option_count += 1;
+            }
+            if parsed.query.check > 0 {
+                option_count += 1;
+            }
+            if parsed.query.changelog {
                 option_count += 1;
             }
+            if dot {
+                option_count += 1;
+                parsed.query.dot = true;
+            }
             
             if option_count > 1 {
-                return Err("error: only one of -i, -s, -l, -m, -o, -e, or -r can be used with -Q".to_string());
+                return Err("error: only one of -i, -s, -l, -m, -n, -o, -e, -r, -u, -g, -k, -c, -dt, or --dot can be used with -Q".to_string());
             }
             
-            if (parsed.query.info
+            if (parsed.query.info > 0
                 || parsed.query.search
                 || parsed.query.list_files
                 || parsed.query.owns
-                || parsed.query.reverse_deps)
+                || parsed.query.reverse_deps > 0
+                || parsed.query.changelog)
                 && parsed.targets.is_empty()
             {
                 return Err("error: no targets specified (use -h for help)".to_string());
             }
             
+            if parsed.query.file {
+                if option_count > 0 && parsed.query.info == 0 && !parsed.query.list_files {
+                    return Err("error: -p can only be combined with -i or -l".to_string());
+                }
+                if parsed.targets.is_empty() {
+                    return Err("error: no package files specified (use -h for help)".to_string());
+                }
+            }
+            
             if parsed.query.manual && !parsed.targets.is_empty() {
                 return Err("error: -Qm does not take targets".to_string());
             }
+            
+            if parsed.query.native && !parsed.targets.is_empty() {
+                return Err("error: -Qn does not take targets".to_string());
+            }
+            
+            if parsed.query.upgrades && !parsed.targets.is_empty() {
+                return Err("error: -Qu does not take targets".to_string());
+            }
+            
+            if parsed.query.orphans && !parsed.targets.is_empty() {
+                return Err("error: -Qdt does not take targets".to_string());
+            }
+            
+            if missing {
+                if !parsed.query.list_files || parsed.query.file {
+                    return Err("error: --missing only applies to -Ql".to_string());
+                }
+                parsed.query.missing = true;
+            }
         }
         Operation::Remove => {
             for ch in flag_chars {
                 match ch {
-                    's' => parsed.remove.recursive = true,
+                    's' => parsed.remove.recursive = parsed.remove.recursive.saturating_add(1),
                     'n' => parsed.remove.nosave = true,
+                    'c' => parsed.remove.cascade = true,
                     'd' => parsed.global.nodeps = parsed.global.nodeps.saturating_add(1),
                     _ => return Err(format!("error: invalid option '-{}' for -R", ch)),
                 }
@@ -412,6 +857,38 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
                 return Err("error: invalid options for -R".to_string());
             }
         }
+        Operation::Database => {
+            for ch in flag_chars {
+                match ch {
+                    'k' => parsed.database.check = true,
+                    _ => return Err(format!("error: invalid option '-{}' for -D", ch)),
+                }
+            }
+            if parsed.database.check {
+                if !parsed.targets.is_empty() || parsed.global.asdeps || parsed.global.asexplicit {
+                    return Err("error: -Dk does not take targets or reason flags".to_string());
+                }
+            } else {
+                if parsed.targets.is_empty() {
+                    return Err("error: no targets specified (use -h for help)".to_string());
+                }
+                if parsed.global.asdeps == parsed.global.asexplicit {
+                    return Err("error: -D requires exactly one of --asdeps or --asexplicit".to_string());
+                }
+            }
+        }
+        Operation::Files => {
+            for ch in flag_chars {
+                match ch {
+                    'y' => parsed.files.refresh = parsed.files.refresh.saturating_add(1),
+                    'q' => parsed.global.quiet = true,
+                    _ => return Err(format!("error: invalid option '-{}' for -F", ch)),
+                }
+            }
+            if parsed.files.refresh == 0 && parsed.targets.is_empty() {
+                return Err("error: no targets specified (use -h for help)".to_string());
+            }
+        }
         Operation::Upgrade => {
             for ch in flag_chars {
                 match ch {
@@ -448,16 +925,136 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
                 return Err("error: history does not accept short operation flags".to_string());
             }
         }
+        Operation::Repos => {
+            if !flag_chars.is_empty() {
+                return Err("error: repos does not accept short operation flags".to_string());
+            }
+            if !parsed.targets.is_empty() {
+                return Err("error: repos does not take targets".to_string());
+            }
+        }
+        Operation::RankMirrors => {
+            if !flag_chars.is_empty() {
+                return Err("error: rankmirrors does not accept short operation flags".to_string());
+            }
+            if !parsed.targets.is_empty() {
+                return Err("error: rankmirrors does not take targets".to_string());
+            }
+        }
+        Operation::CleanOrphans => {
+            if !flag_chars.is_empty() {
+                return Err("error: --clean-orphans does not accept short operation flags".to_string());
+            }
+            if !parsed.targets.is_empty() {
+                return Err("error: --clean-orphans does not take targets".to_string());
+            }
+        }
+        Operation::ConfigTest => {
+            if !flag_chars.is_empty() {
+                return Err("error: --config-test does not accept short operation flags".to_string());
+            }
+            if !parsed.targets.is_empty() {
+                return Err("error: --config-test does not take targets".to_string());
+            }
+        }
+        Operation::Export => {
+            if !flag_chars.is_empty() {
+                return Err("error: export does not accept short operation flags".to_string());
+            }
+            if !parsed.targets.is_empty() {
+                return Err("error: export does not take targets".to_string());
+            }
+        }
+        Operation::Import => {
+            if !flag_chars.is_empty() {
+                return Err("error: import does not accept short operation flags".to_string());
+            }
+            if parsed.targets.len() != 1 {
+                return Err("error: import takes exactly one manifest file".to_string());
+            }
+        }
+        Operation::Verify => {
+            if !flag_chars.is_empty() {
+                return Err("error: verify does not accept short operation flags".to_string());
+            }
+            if parsed.targets.is_empty() {
+                return Err("error: no package file specified for verify".to_string());
+            }
+        }
         Operation::Help => {}
     }
     
     if parsed.op != Operation::Sync {
-        if parsed.global.needed || parsed.global.asdeps || parsed.global.asexplicit || parsed.global.noscriptlet {
-            return Err("error: --needed/--asdeps/--asexplicit/--noscriptlet only apply to -S".to_string());
+        if parsed.global.needed && parsed.op != Operation::Upgrade {
+            return Err("error: --needed only applies to -S/-U".to_string());
+        }
+        if (parsed.global.asdeps || parsed.global.asexplicit) && parsed.op != Operation::Database {
+            return Err("error: --asdeps/--asexplicit only apply to -S/-D".to_string());
+        }
+        if parsed.global.noscriptlet {
+            return Err("error: --noscriptlet only applies to -S".to_string());
         }
         if !parsed.global.overwrite.is_empty() {
             return Err("error: --overwrite only applies to -S".to_string());
         }
+        if parsed.global.dbonly && !matches!(parsed.op, Operation::Upgrade | Operation::Remove) {
+            return Err("error: --dbonly only applies to -S/-U/-R".to_string());
+        }
+        if parsed.global.disable_dl_timeout && parsed.op != Operation::Upgrade {
+            return Err("error: --disable-download-timeout only applies to -S/-U".to_string());
+        }
+    }
+    if parsed.global.disable_dl_timeout {
+        let sync = &parsed.sync;
+        if sync.search || sync.info > 0 || sync.list || sync.groups || sync.clean_cache > 0 {
+            return Err("error: --disable-download-timeout cannot be combined with -s/-i/-l/-g/-c".to_string());
+        }
+    }
+    
+    if parsed.global.limit.is_some() && !(parsed.sync.search || parsed.query.search) {
+        return Err("error: --limit only applies to -Ss/-Qs".to_string());
+    }
+    if (parsed.global.name_only || parsed.global.exact) && !(parsed.sync.search || parsed.query.search) {
+        return Err("error: --name-only/--exact only apply to -Ss/-Qs".to_string());
+    }
+    
+    if parsed.op != Operation::Doctor
+        && (!parsed.doctor.checks.is_empty() || parsed.doctor.list_checks || parsed.doctor.check_mirrors)
+    {
+        return Err("error: --check/--list-checks/--check-mirrors only apply to doctor".to_string());
+    }
+    
+    if parsed.op != Operation::RankMirrors && parsed.mirrors.samples.is_some() {
+        return Err("error: --samples only applies to rankmirrors".to_string());
+    }
+    
+    if parsed.op != Operation::History && (parsed.history.older_than.is_some() || parsed.history.keep.is_some()) {
+        return Err("error: --older-than/--keep only apply to history prune".to_string());
+    }
+    if parsed.op != Operation::History && (parsed.history.op.is_some() || parsed.history.since.is_some() || parsed.history.raw) {
+        return Err("error: --op/--since/--raw only apply to history".to_string());
+    }
+    
+    if !parsed.global.ignore.is_empty() && parsed.op != Operation::Sync && parsed.op != Operation::Upgrade {
+        return Err("error: --ignore only applies to -S/-U".to_string());
+    }
+    if parsed.global.time && parsed.op != Operation::Sync {
+        return Err("error: --time only applies to -S".to_string());
+    }
+    if parsed.global.print_only {
+        if parsed.op != Operation::Sync && parsed.op != Operation::Remove && parsed.op != Operation::Upgrade {
+            return Err("error: --print only applies to -S/-R/-U".to_string());
+        }
+        let sync = &parsed.sync;
+        if sync.search || sync.info > 0 || sync.list || sync.groups || sync.print_urls || sync.refresh > 0 || sync.clean_cache > 0 {
+            return Err("error: --print cannot be combined with -s/-i/-l/-g/-p/-y/-c".to_string());
+        }
+        if parsed.global.test {
+            return Err("error: --print and --test cannot be used together".to_string());
+        }
+    }
+    if parsed.global.explain && parsed.op != Operation::Sync && parsed.op != Operation::Upgrade {
+        return Err("error: --explain only applies to -S/-U".to_string());
     }
     
     if (parsed.op == Operation::Query || parsed.op == Operation::Why) && parsed.global.nodeps > 0
@@ -468,6 +1065,9 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
     if parsed.global.compact && parsed.global.verbose {
         return Err("error: --compact and --verbose cannot be used together".to_string());
     }
+    if parsed.global.quiet && parsed.global.verbose {
+        return Err("error: --quiet and --verbose cannot be used together".to_string());
+    }
     
     if parsed.global.strict {
         if parsed.global.nodeps > 0 {
@@ -476,12 +1076,18 @@ fn parse_args(args: &[String]) -> std::result::Result<ParsedArgs, String> {
         if parsed.global.noscriptlet {
             return Err("error: --strict disallows --noscriptlet".to_string());
         }
+        if parsed.global.dbonly {
+            return Err("error: --strict disallows --dbonly".to_string());
+        }
         if !parsed.global.overwrite.is_empty() {
             return Err("error: --strict disallows --overwrite".to_string());
         }
         if parsed.global.insecure_skip_signatures {
             return Err("error: --strict disallows --insecure-skip-signatures".to_string());
         }
+        if is_partial_upgrade(&parsed) {
+            return Err("error: --strict disallows partial upgrades (-Sy <pkg> without -u); use -Syu <pkg>".to_string());
+        }
     }
     
     Ok(parsed)
@@ -491,20 +1097,40 @@ fn handle_sync(parsed: &ParsedArgs) -> Result<()> {
     let flags = &parsed.sync;
     
     // Check root for install/upgrade/sync
-    if !flags.search && !flags.info && !utils::is_root() {
+    if !flags.search
+        && flags.info == 0
+        && !flags.list
+        && !flags.print_urls
+        && !flags.groups
+        && !parsed.global.print_only
+        && !utils::is_root()
+    {
         eprintln!("{}", "error: you cannot perform this operation unless you are root.".red());
         std::process::exit(1);
     }
     
     if flags.search {
-        search_packages(&parsed.global, &parsed.targets)?;
+        search_packages(&parsed.global, &parsed.targets, flags.aur_search)?;
         return Ok(());
     }
     
-    if flags.info {
-        for pkg in &parsed.targets {
-            show_sync_info(&parsed.global, pkg)?;
-        }
+    if flags.info > 0 {
+        search::show_sync_package_infos(&parsed.global, &parsed.targets, flags.info)?;
+        return Ok(());
+    }
+    
+    if flags.list {
+        search::list_repo_packages(&parsed.global, &parsed.targets)?;
+        return Ok(());
+    }
+    
+    if flags.groups {
+        search::list_sync_group(&parsed.global, &parsed.targets)?;
+        return Ok(());
+    }
+    
+    if flags.print_urls {
+        install::print_sync_urls(&parsed.global, flags.upgrade, &parsed.targets)?;
         return Ok(());
     }
     
@@ -514,21 +1140,33 @@ fn handle_sync(parsed: &ParsedArgs) -> Result<()> {
         return Ok(());
     }
     
+    if is_partial_upgrade(parsed) && !parsed.global.json {
+        eprintln!(
+            "{} {}",
+            "warning:".yellow().bold(),
+            "refreshing databases without -u is a partial upgrade; new packages may link against libraries your system does not have yet. Use -Syu instead.".yellow()
+        );
+    }
+    
     let refresh = flags.refresh;
     let upgrade = flags.upgrade;
-    if refresh || upgrade || parsed.targets.is_empty() {
+    if !parsed.global.print_only {
         alpm_ops::preflight_transaction(&parsed.global)?;
+    }
+    if refresh > 0 || upgrade || parsed.targets.is_empty() {
         install::sync_install(
             &parsed.global,
             refresh,
             upgrade,
+            flags.downgrade,
+            flags.download_only,
+            !flags.no_news,
             parsed.targets.as_slice(),
         )?;
         return Ok(());
     }
     
-    alpm_ops::preflight_transaction(&parsed.global)?;
-    install_packages(parsed.targets.clone(), &parsed.global)?;
+    install_packages(parsed.targets.clone(), &parsed.global, flags.download_only)?;
     
     Ok(())
 }
@@ -536,8 +1174,13 @@ fn handle_sync(parsed: &ParsedArgs) -> Result<()> {
 fn handle_query(parsed: &ParsedArgs) -> Result<()> {
     let flags = &parsed.query;
     
-    if flags.info {
-        search::show_local_package_infos(&parsed.global, &parsed.targets)?;
+    if flags.file {
+        search::query_package_files(&parsed.global, &parsed.targets, flags.list_files, flags.info)?;
+        return Ok(());
+    }
+    
+    if flags.info > 0 {
+        search::show_local_package_infos(&parsed.global, &parsed.targets, flags.info)?;
         return Ok(());
     }
     
@@ -547,7 +1190,11 @@ fn handle_query(parsed: &ParsedArgs) -> Result<()> {
     }
     
     if flags.list_files {
-        search::list_package_files(&parsed.global, &parsed.targets)?;
+        if flags.missing {
+            search::list_missing_files(&parsed.global, &parsed.targets)?;
+        } else {
+            search::list_package_files(&parsed.global, &parsed.targets)?;
+        }
         return Ok(());
     }
     
@@ -556,6 +1203,11 @@ fn handle_query(parsed: &ParsedArgs) -> Result<()> {
         return Ok(());
     }
     
+    if flags.native {
+        search::list_native_packages(&parsed.global)?;
+        return Ok(());
+    }
+    
     if flags.owns {
         search::query_owns(&parsed.global, &parsed.targets)?;
         return Ok(());
@@ -570,8 +1222,38 @@ fn handle_query(parsed: &ParsedArgs) -> Result<()> {
         return Ok(());
     }
     
-    if flags.reverse_deps {
-        search::query_reverse_dependencies(&parsed.global, &parsed.targets)?;
+    if flags.reverse_deps > 0 {
+        search::query_reverse_dependencies(&parsed.global, &parsed.targets, flags.reverse_deps > 1)?;
+        return Ok(());
+    }
+    
+    if flags.dot {
+        search::print_dependency_dot(&parsed.global, &parsed.targets)?;
+        return Ok(());
+    }
+    
+    if flags.upgrades {
+        search::list_upgradable(&parsed.global)?;
+        return Ok(());
+    }
+    
+    if flags.orphans {
+        search::list_orphans(&parsed.global)?;
+        return Ok(());
+    }
+    
+    if flags.groups {
+        search::query_groups(&parsed.global, &parsed.targets)?;
+        return Ok(());
+    }
+    
+    if flags.changelog {
+        search::show_changelog(&parsed.global, &parsed.targets)?;
+        return Ok(());
+    }
+    
+    if flags.check > 0 {
+        search::check_package_files(&parsed.global, &parsed.targets, flags.check)?;
         return Ok(());
     }
     
@@ -585,30 +1267,95 @@ fn handle_query(parsed: &ParsedArgs) -> Result<()> {
 }
 
 fn handle_remove(parsed: &ParsedArgs) -> Result<()> {
+    if !parsed.global.print_only {
+        if !utils::is_root() {
+            eprintln!("{}", "error: you cannot perform this operation unless you are root.".red());
+            std::process::exit(1);
+        }
+        alpm_ops::ensure_db_unlocked(&parsed.global)?;
+    }
+    remove_packages(parsed.targets.clone(), &parsed.remove, &parsed.global)?;
+    
+    Ok(())
+}
+
+/// `--clean-orphans`: `-Rns` on everything `-Qdt` lists, in one transaction.
+fn handle_clean_orphans(parsed: &ParsedArgs) -> Result<()> {
+    let orphans = {
+        let handle = alpm_ops::init_handle(&parsed.global)?;
+        search::orphan_names(&handle)
+    };
+    if orphans.is_empty() {
+        println!(" {}", "no orphans found".yellow());
+        return Ok(());
+    }
+    let remove = RemoveFlags {
+        recursive: 1,
+        nosave: true,
+        ..RemoveFlags::default()
+    };
+    if !parsed.global.print_only {
+        if !utils::is_root() {
+            eprintln!("{}", "error: you cannot perform this operation unless you are root.".red());
+            std::process::exit(1);
+        }
+        alpm_ops::ensure_db_unlocked(&parsed.global)?;
+    }
+    remove_packages(orphans, &remove, &parsed.global)
+}
+
+fn handle_database(parsed: &ParsedArgs) -> Result<()> {
+    if parsed.database.check {
+        return doctor::check_database(&parsed.global);
+    }
     if !utils::is_root() {
         eprintln!("{}", "error: you cannot perform this operation unless you are root.".red());
         std::process::exit(1);
     }
     
     alpm_ops::ensure_db_unlocked(&parsed.global)?;
-    remove_packages(parsed.targets.clone(), &parsed.remove, &parsed.global)?;
-    
+    install::change_install_reasons(&parsed.global, &parsed.targets)
+}
+
+fn handle_files(parsed: &ParsedArgs) -> Result<()> {
+    if parsed.files.refresh > 0 {
+        if !utils::is_root() {
+            eprintln!("{}", "error: you cannot perform this operation unless you are root.".red());
+            std::process::exit(1);
+        }
+        alpm_ops::ensure_db_unlocked(&parsed.global)?;
+        files::refresh(&parsed.global, parsed.files.refresh > 1)?;
+    }
+    if !parsed.targets.is_empty() {
+        files::search(&parsed.global, &parsed.targets)?;
+    }
     Ok(())
 }
 
 fn handle_upgrade(parsed: &ParsedArgs) -> Result<()> {
+    if !parsed.global.print_only {
+        if !utils::is_root() {
+            eprintln!("{}", "error: you cannot perform this operation unless you are root.".red());
+            std::process::exit(1);
+        }
+        alpm_ops::preflight_transaction(&parsed.global)?;
+    }
+    install::install_local(&parsed.global, &parsed.targets)?;
+    Ok(())
+}
+
+fn handle_import(parsed: &ParsedArgs) -> Result<()> {
     if !utils::is_root() {
         eprintln!("{}", "error: you cannot perform this operation unless you are root.".red());
         std::process::exit(1);
     }
     
     alpm_ops::preflight_transaction(&parsed.global)?;
-    install::install_local(&parsed.global, &parsed.targets)?;
-    Ok(())
+    manifest::import(&parsed.global, &parsed.targets[0])
 }
 
 fn handle_doctor(parsed: &ParsedArgs) -> Result<()> {
-    doctor::run(&parsed.global)
+    doctor::run(&parsed.global, &parsed.doctor)
 }
 
 fn handle_why(parsed: &ParsedArgs) -> Result<()> {
@@ -616,7 +1363,18 @@ fn handle_why(parsed: &ParsedArgs) -> Result<()> {
 }
 
 fn handle_history(parsed: &ParsedArgs) -> Result<()> {
-    history::show(&parsed.global, &parsed.targets)
+    if parsed.targets.first().map(|a| a.as_str()) == Some("undo") {
+        let Some(id) = parsed.targets.get(1) else {
+            anyhow::bail!("usage: rustpack history undo <id>");
+        };
+        if !utils::is_root() {
+            eprintln!("{}", "error: you cannot perform this operation unless you are root.".red());
+            std::process::exit(1);
+        }
+        alpm_ops::preflight_transaction(&parsed.global)?;
+        return install::undo_transaction(&parsed.global, id);
+    }
+    history::show(&parsed.global, &parsed.history, &parsed.targets)
 }
 
 fn print_usage() {
@@ -627,30 +1385,72 @@ fn print_usage() {
     println!("{} {}", "Usage:".bold(), "rustpack <operation> [options] [targets]");
 
     print_help_section("Operations");
-    print_help_row("-S [y|u|s|i]", "Sync/upgrade, search, or info", LEFT_WIDTH);
-    print_help_row("-Q [i|s|l|m|o|e|r]", "Query installed packages", LEFT_WIDTH);
-    print_help_row("-R [s|n]", "Remove packages", LEFT_WIDTH);
+    print_help_row("-S [y|u|s|i|l|w|p|g]", "Sync/upgrade, search, info, list, download, URLs", LEFT_WIDTH);
+    print_help_row("-Q [i|s|l|m|n|o|e|r|u|g|k|c|p]", "Query installed packages", LEFT_WIDTH);
+    print_help_row("-R [s|n|c]", "Remove packages", LEFT_WIDTH);
     print_help_row("-U <pkgfile>", "Install local package file", LEFT_WIDTH);
-    print_help_row("--why <pkg>", "Explain why a package is installed", LEFT_WIDTH);
+    print_help_row("-D --asdeps|--asexplicit", "Change install reason of packages", LEFT_WIDTH);
+    print_help_row("-Dk", "Check local database consistency", LEFT_WIDTH);
+    print_help_row("-F [y] <file>", "Find the repository package owning a file", LEFT_WIDTH);
+    print_help_row("why, --why <pkg>", "Explain why a package is installed", LEFT_WIDTH);
     print_help_row("doctor", "Run health checks (Arch/CachyOS aware)", LEFT_WIDTH);
     print_help_row("history", "Show transaction timeline", LEFT_WIDTH);
+    print_help_row("repos, --list-repos", "List repositories and expanded servers", LEFT_WIDTH);
+    print_help_row("rankmirrors", "Time each mirror and print a ranked mirrorlist", LEFT_WIDTH);
+    print_help_row("--config-test", "Validate pacman.conf and exit", LEFT_WIDTH);
+    print_help_row("export", "Print explicit repo packages as a manifest", LEFT_WIDTH);
+    print_help_row("import <manifest>", "Install packages missing from a manifest", LEFT_WIDTH);
+    print_help_row("verify <pkgfile>", "Check a package file's archive and signature", LEFT_WIDTH);
 
     print_help_section("Examples");
     print_help_row("rustpack -Ss firefox", "Search for firefox", LEFT_WIDTH);
+    print_help_row("rustpack -Ss --limit 10 python", "Show the 10 most relevant matches", LEFT_WIDTH);
+    print_help_row("rustpack -Ss --name-only vim", "Match package names, not descriptions", LEFT_WIDTH);
+    print_help_row("rustpack -Ss --exact vim", "Match the whole package name", LEFT_WIDTH);
+    print_help_row("rustpack -Ss --aur-search yay", "Also search the AUR (search only)", LEFT_WIDTH);
     print_help_row("rustpack -S firefox", "Install firefox", LEFT_WIDTH);
+    print_help_row("rustpack -S extra/vim", "Install vim from the extra repo", LEFT_WIDTH);
+    print_help_row("rustpack -Qii pacman", "Show info and backup file states", LEFT_WIDTH);
+    print_help_row("rustpack -S base-devel", "Install every member of a group", LEFT_WIDTH);
     print_help_row("rustpack -Syu", "Full system upgrade", LEFT_WIDTH);
+    print_help_row("rustpack -Syuu", "Full upgrade allowing downgrades", LEFT_WIDTH);
+    print_help_row("rustpack -Syu --no-news", "Upgrade without checking Arch news", LEFT_WIDTH);
+    print_help_row("rustpack -Sl core", "List all packages in core", LEFT_WIDTH);
+    print_help_row("rustpack -Sw firefox", "Download firefox into the cache only", LEFT_WIDTH);
+    print_help_row("rustpack -Sp firefox", "Print download URLs for firefox and deps", LEFT_WIDTH);
+    print_help_row("rustpack -Rs --print firefox", "List what -Rs firefox would remove", LEFT_WIDTH);
     print_help_row("rustpack -Q", "List installed packages", LEFT_WIDTH);
     print_help_row("rustpack -Ql bash", "List files for bash", LEFT_WIDTH);
+    print_help_row("rustpack -Ql --missing bash", "List bash files missing on disk", LEFT_WIDTH);
+    print_help_row("rustpack -Qq", "List installed package names only", LEFT_WIDTH);
     print_help_row("rustpack -Qm", "List foreign packages", LEFT_WIDTH);
+    print_help_row("rustpack -Qn", "List native (repository) packages", LEFT_WIDTH);
+    print_help_row("rustpack -Qu", "List packages with available upgrades", LEFT_WIDTH);
+    print_help_row("rustpack -Qdt", "List orphaned dependencies", LEFT_WIDTH);
+    print_help_row("rustpack --clean-orphans", "Remove all orphans (-Rns) after confirmation", LEFT_WIDTH);
+    print_help_row("rustpack -Qg", "List installed packages by group", LEFT_WIDTH);
+    print_help_row("rustpack -Qk bash", "Check bash's files exist on disk", LEFT_WIDTH);
+    print_help_row("rustpack -Qc bash", "Show the changelog of bash", LEFT_WIDTH);
+    print_help_row("rustpack -Qp ./foo.pkg.tar.zst", "Show a package file's metadata", LEFT_WIDTH);
+    print_help_row("rustpack -Sg gnome", "List members of the gnome group", LEFT_WIDTH);
     print_help_row("rustpack -Qe", "List explicitly installed packages", LEFT_WIDTH);
     print_help_row("rustpack --why libva", "Explain install reason chain", LEFT_WIDTH);
     print_help_row("rustpack -Qr glibc", "Show reverse dependencies of glibc", LEFT_WIDTH);
+    print_help_row("rustpack -Qrr glibc", "Show the full reverse-dependency tree", LEFT_WIDTH);
+    print_help_row("rustpack -Q --dot > deps.dot", "Dependency graph in Graphviz DOT", LEFT_WIDTH);
     print_help_row("rustpack -Qo /usr/bin/vi", "Find owning package", LEFT_WIDTH);
     print_help_row("rustpack doctor", "Run package-manager health checks", LEFT_WIDTH);
+    print_help_row("rustpack doctor --check keyring", "Run only the keyring check", LEFT_WIDTH);
+    print_help_row("rustpack doctor --list-checks", "List available doctor checks", LEFT_WIDTH);
+    print_help_row("rustpack doctor --check-mirrors", "Also probe each repo's first mirror", LEFT_WIDTH);
+    print_help_row("rustpack rankmirrors --samples 5", "Rank mirrors on 5 fetches each", LEFT_WIDTH);
     print_help_row("rustpack history", "Show recent transactions", LEFT_WIDTH);
     print_help_row("rustpack history show <id>", "Show one transaction", LEFT_WIDTH);
+    print_help_row("rustpack history undo <id>", "Revert one transaction", LEFT_WIDTH);
+    print_help_row("rustpack history --op sync", "Show only sync transactions", LEFT_WIDTH);
     print_help_row("rustpack -R firefox", "Remove firefox", LEFT_WIDTH);
     print_help_row("rustpack -Rns firefox", "Remove firefox and unused deps", LEFT_WIDTH);
+    print_help_row("rustpack -Rss firefox", "Also remove explicitly installed deps", LEFT_WIDTH);
     print_help_row(
         "rustpack -U ./pkg.pkg.tar.zst",
         "Install a local package file",
@@ -661,11 +1461,29 @@ fn print_usage() {
     print_help_section("Notes");
     print_help_note("Use '--' to stop option parsing (example: rustpack -S -- -weirdpkg)");
     print_help_note("Use '--test' to simulate changes without committing");
+    print_help_note("Use '--print' with -S/-R/-U to list resolved targets only (no root, no prompts)");
+    print_help_note("Use '--explain' to show which targets pull in each dependency");
+    print_help_note("Use '--time' with -S/-Syu to print how long refresh, resolve and commit took");
     print_help_note("Common options: --noconfirm --needed --overwrite --asdeps --asexplicit");
-    print_help_note("                --root --dbpath --cachedir --strict --compact --verbose --json");
+    print_help_note("                --ignore <pkg,...> (hold packages back like IgnorePkg)");
+    print_help_note("                --assume-installed <pkg[=ver]> (treat a dependency as satisfied)");
+    print_help_note("                --parallel <n> (concurrent downloads; overrides ParallelDownloads)");
+    print_help_note("                --yes-to <install,remove,replace,conflict>");
+    print_help_note("                --download-warn <size> (confirm downloads larger than e.g. 500M)");
+    print_help_note("                --sysroot --config --root --dbpath --cachedir --logfile --gpgdir --arch");
+    print_help_note("                --strict --compact --verbose --json");
+    print_help_note("Use '--noprogress' to print one line per download/package instead of progress bars (default when not a tty)");
+    print_help_note("Use '-q'/'--quiet' with -S/-Q listings to print bare package names (e.g. -Ssq, -Qq)");
+    print_help_note("Use '--color <auto|always|never>' to control colors (NO_COLOR is honored in auto mode)");
+    print_help_note("Output width: --width <n> to fit descriptions, --no-truncate to print them in full");
     print_help_note("Emergency only: --insecure-skip-signatures (disables signature checks)");
     print_help_note("Dependency options: -d/-dd (--nodeps), --noscriptlet");
+    print_help_note("Use '--dbonly' with -S/-U/-R to update the package database without touching files");
+    print_help_note("Slow mirrors: --disable-download-timeout (-S/-U) keeps stalled downloads alive");
     print_help_note("Cache clean: -Sc (unused) or -Scc (all)");
+    print_help_note("History retention: rustpack history prune --older-than 90d (units: s m h d w) or --keep 500");
+    print_help_note("                   rustpack history clear (asks before wiping the log)");
+    print_help_note("History filters: --op <op> --since YYYY-MM-DD; --raw prints UNIX timestamps");
 }
 
 fn print_help_section(title: &str) {
@@ -681,6 +1499,16 @@ fn print_help_note(note: &str) {
     println!("  {}", note.dimmed());
 }
 
+/// `-Sy <targets>` without `-u`: installs against fresh databases while the rest
+/// of the system stays at the old versions.
+fn is_partial_upgrade(parsed: &ParsedArgs) -> bool {
+    parsed.op == Operation::Sync
+        && parsed.sync.refresh > 0
+        && !parsed.sync.upgrade
+        && !parsed.sync.download_only
+        && !parsed.targets.is_empty()
+}
+
 fn emit_safety_warnings(global: &GlobalFlags) {
     if global.strict || global.json {
         return;
@@ -699,6 +1527,13 @@ fn emit_safety_warnings(global: &GlobalFlags) {
             "scriptlets are disabled; some packages may not configure correctly".yellow()
         );
     }
+    if global.dbonly {
+        eprintln!(
+            "{} {}",
+            "warning:".yellow().bold(),
+            "--dbonly only updates the package database; files on disk are left untouched".yellow()
+        );
+    }
     if !global.overwrite.is_empty() {
         eprintln!(
             "{} {}",
@@ -726,62 +1561,62 @@ fn json_escape(input: &str) -> String {
 
 fn print_runtime_error(global: &GlobalFlags, err: &anyhow::Error) {
     let msg = err.to_string();
+    let classified = RustpackError::classify(err);
     if global.json {
         if msg == "__RUSTPACK_JSON_DOCTOR_FAILED__" {
             return;
         }
-        println!("{{\"error\":\"{}\"}}", json_escape(&msg));
-        return;
-    }
-    let lower = msg.to_ascii_lowercase();
-    if lower.contains("db.lck") || lower.contains("unable to lock database") || lower.contains("database is locked") {
-        eprintln!(
-            "{} {}",
-            "error:".red().bold(),
-            "package database is locked by another process.".red()
-        );
-        eprintln!("{} wait for the other package manager process to finish.", "hint:".cyan().bold());
-        eprintln!(
-            "{} if no package manager is running, remove the stale lock file manually.",
-            "hint:".cyan().bold()
+        println!(
+            "{{\"error\":\"{}\",\"kind\":\"{}\"}}",
+            json_escape(&msg),
+            classified.kind()
         );
         return;
     }
-    if lower.contains("pgp signature")
-        || lower.contains("signature from")
-        || lower.contains("is not valid (invalid or corrupted database")
-    {
-        eprintln!("{} {}", "error:".red().bold(), msg);
-        eprintln!(
-            "{} fix keyrings first: sudo pacman-key --init && sudo pacman-key --populate archlinux cachyos",
-            "hint:".cyan().bold()
-        );
-        eprintln!(
-            "{} refresh keyring packages: sudo pacman -Sy --needed archlinux-keyring cachyos-keyring",
-            "hint:".cyan().bold()
-        );
-        eprintln!(
-            "{} emergency bypass: rerun once with --insecure-skip-signatures, then repair keyrings immediately.",
-            "hint:".cyan().bold()
-        );
-        return;
+    match classified {
+        RustpackError::DbLocked(_) => print_db_locked_error(),
+        RustpackError::SignatureError(_) => print_signature_error(&msg),
+        _ => eprintln!("{} {}", "error:".red().bold(), msg),
     }
-    eprintln!("{} {}", "error:".red().bold(), msg);
 }
 
-fn install_packages(packages: Vec<String>, global: &GlobalFlags) -> Result<()> {
-    install::install_packages(&packages, global)?;
-    
-    Ok(())
+fn print_db_locked_error() {
+    eprintln!(
+        "{} {}",
+        "error:".red().bold(),
+        "package database is locked by another process.".red()
+    );
+    eprintln!("{} wait for the other package manager process to finish.", "hint:".cyan().bold());
+    eprintln!(
+        "{} if no package manager is running, remove the stale lock file manually.",
+        "hint:".cyan().bold()
+    );
+}
+
+fn print_signature_error(msg: &str) {
+    eprintln!("{} {}", "error:".red().bold(), msg);
+    eprintln!(
+        "{} fix keyrings first: sudo pacman-key --init && sudo pacman-key --populate archlinux cachyos",
+        "hint:".cyan().bold()
+    );
+    eprintln!(
+        "{} refresh keyring packages: sudo pacman -Sy --needed archlinux-keyring cachyos-keyring",
+        "hint:".cyan().bold()
+    );
+    eprintln!(
+        "{} emergency bypass: rerun once with --insecure-skip-signatures, then repair keyrings immediately.",
+        "hint:".cyan().bold()
+    );
 }
 
-fn search_packages(global: &GlobalFlags, queries: &[String]) -> Result<()> {
-    search::search_repos(global, queries)?;
+fn install_packages(packages: Vec<String>, global: &GlobalFlags, download_only: bool) -> Result<()> {
+    install::install_packages(&packages, global, download_only)?;
+    
     Ok(())
 }
 
-fn show_sync_info(global: &GlobalFlags, package: &str) -> Result<()> {
-    search::show_sync_package_info(global, package)?;
+fn search_packages(global: &GlobalFlags, queries: &[String], aur: bool) -> Result<()> {
+    search::search_repos(global, queries, aur)?;
     Ok(())
 }
 
@@ -800,3 +1635,37 @@ fn remove_packages(packages: Vec<String>, remove: &RemoveFlags, global: &GlobalF
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn args(list: &[&str]) -> Vec<String> {
+        std::iter::once("rustpack").chain(list.iter().copied()).map(|s| s.to_string()).collect()
+    }
+    
+    #[test]
+    fn test_yes_to_accepts_lists_and_repeats() {
+        let parsed = parse_args(&args(&["-S", "--yes-to", "install, replace", "--yes-to=install,conflict", "foo"])).unwrap();
+        assert_eq!(parsed.global.yes_to, vec!["install", "replace", "conflict"]);
+        assert_eq!(parsed.targets, vec!["foo"]);
+    }
+    
+    #[test]
+    fn test_yes_to_rejects_unknown_and_missing_kinds() {
+        let err = parse_args(&args(&["-S", "--yes-to", "install,everything", "foo"])).err().unwrap();
+        assert!(err.contains("invalid --yes-to kind 'everything'"));
+        let err = parse_args(&args(&["-S", "--yes-to"])).err().unwrap();
+        assert!(err.contains("--yes-to requires a value"));
+    }
+    
+    #[test]
+    fn test_yes_to_pre_answers_each_kind() {
+        for kind in cli::YES_TO_KINDS {
+            let parsed = parse_args(&args(&["-S", "--yes-to", kind, "foo"])).unwrap();
+            for other in cli::YES_TO_KINDS {
+                assert_eq!(parsed.global.pre_answered(other), other == kind, "--yes-to {}", kind);
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use alpm::PackageReason;
+use colored::Colorize;
+use std::fs;
+
+use crate::alpm_ops;
+use crate::cli::GlobalFlags;
+use crate::install;
+use crate::search;
+
+/// Prints explicitly installed packages that are available from the sync
+/// repositories, one name per line, for `rustpack import` on another system.
+pub fn export(global: &GlobalFlags) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let native_names = search::sync_package_names(&handle);
+    let mut names: Vec<&str> = handle
+        .localdb()
+        .pkgs()
+        .iter()
+        .filter(|pkg| pkg.reason() == PackageReason::Explicit && native_names.contains(pkg.name()))
+        .map(|pkg| pkg.name())
+        .collect();
+    names.sort_unstable();
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+/// Reads a manifest written by `export` (blank lines and `#` comments are
+/// ignored) and installs the packages that are not present yet.
+pub fn import(global: &GlobalFlags, path: &str) -> Result<()> {
+    let content = fs::read_to_string(path).context(format!("Failed to read {}", path))?;
+    let handle = alpm_ops::init_handle(global)?;
+    let localdb = handle.localdb();
+    let native_names = search::sync_package_names(&handle);
+
+    let mut missing = Vec::new();
+    let mut not_found = Vec::new();
+    let mut installed = 0usize;
+    for line in content.lines() {
+        let name = line.split('#').next().unwrap_or("").trim();
+        if name.is_empty() {
+            continue;
+        }
+        if localdb.pkg(name).is_ok() {
+            installed += 1;
+        } else if native_names.contains(name) {
+            missing.push(name.to_string());
+        } else {
+            not_found.push(name.to_string());
+        }
+    }
+    drop(handle);
+
+    if !global.compact {
+        println!(
+            ":: {} {} already installed, {} to install",
+            "Manifest:".cyan().bold(),
+            installed,
+            missing.len()
+        );
+    }
+    if !not_found.is_empty() {
+        eprintln!(
+            "{} not found in the repositories: {}",
+            "warning:".yellow().bold(),
+            not_found.join(" ")
+        );
+    }
+    if missing.is_empty() {
+        println!(" {}", "there is nothing to do".yellow());
+        return Ok(());
+    }
+
+    let mut global = global.clone();
+    global.needed = true;
+    install::install_packages(&missing, &global, false)
+}
@@ -0,0 +1,183 @@
+use anyhow::{Result, bail};
+use colored::Colorize;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::alpm_ops;
+use crate::cli::{GlobalFlags, MirrorFlags};
+use crate::config::PacmanConfig;
+use crate::download;
+use crate::history;
+use crate::install::format_bytes;
+
+const DEFAULT_SAMPLES: u32 = 3;
+/// Mirrors probed at once: enough to finish quickly without saturating the link.
+const MAX_CONCURRENT: usize = 4;
+
+struct Mirror {
+    /// As written in pacman.conf or the mirrorlist, placeholders included.
+    server: String,
+    /// `<server>/<repo>.db` for the first repository using the server.
+    probe_url: String,
+}
+
+struct Ranking {
+    latency: Duration,
+    bytes_per_sec: f64,
+}
+
+fn json_escape(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+/// Every distinct HTTP(S) server of the configured repositories, in config order.
+fn collect_mirrors(config: &PacmanConfig) -> Vec<Mirror> {
+    let mut mirrors: Vec<Mirror> = Vec::new();
+    for repo in &config.repositories {
+        for (server, expanded) in repo.servers.iter().zip(alpm_ops::expanded_servers(config, repo)) {
+            if !expanded.starts_with("http://") && !expanded.starts_with("https://") {
+                continue;
+            }
+            if mirrors.iter().any(|m| &m.server == server) {
+                continue;
+            }
+            mirrors.push(Mirror {
+                server: server.clone(),
+                probe_url: format!("{}/{}.db", expanded.trim_end_matches('/'), repo.name),
+            });
+        }
+    }
+    mirrors
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[values.len() / 2]
+}
+
+/// Fetches the probe file `samples` times and keeps the median latency and
+/// throughput, so one slow answer does not sink an otherwise good mirror.
+fn probe(mirror: &Mirror, samples: u32) -> Result<Ranking> {
+    let mut latencies = Vec::new();
+    let mut rates = Vec::new();
+    for _ in 0..samples {
+        let timing = download::time_fetch(&mirror.probe_url)?;
+        latencies.push(timing.latency.as_secs_f64());
+        rates.push(timing.bytes as f64 / timing.total.as_secs_f64().max(0.001));
+    }
+    Ok(Ranking {
+        latency: Duration::from_secs_f64(median(latencies)),
+        bytes_per_sec: median(rates),
+    })
+}
+
+/// Probes the mirrors on up to `MAX_CONCURRENT` threads, each taking every
+/// `MAX_CONCURRENT`-th mirror.
+fn probe_all(mirrors: &[Mirror], samples: u32) -> Vec<Result<Ranking>> {
+    let workers = MAX_CONCURRENT.min(mirrors.len());
+    let mut results: Vec<Option<Result<Ranking>>> = (0..mirrors.len()).map(|_| None).collect();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|worker| {
+                scope.spawn(move || {
+                    (worker..mirrors.len())
+                        .step_by(workers)
+                        .map(|idx| (idx, probe(&mirrors[idx], samples)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (idx, result) in handle.join().unwrap_or_default() {
+                results[idx] = Some(result);
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| Err(anyhow::anyhow!("probe did not finish"))))
+        .collect()
+}
+
+/// `rankmirrors`: times every configured mirror and prints them fastest first
+/// as `Server =` lines; unreachable mirrors follow, commented out.
+pub fn rank(global: &GlobalFlags, flags: &MirrorFlags) -> Result<()> {
+    let config = alpm_ops::effective_config(global)?;
+    download::set_xfer_command(config.xfer_command.as_deref());
+    let mirrors = collect_mirrors(&config);
+    if mirrors.is_empty() {
+        bail!("no HTTP(S) mirrors configured");
+    }
+    let samples = flags.samples.unwrap_or(DEFAULT_SAMPLES);
+    // A mirror that only answers on a retry should not rank well
+    if global.retries.is_none() {
+        download::set_retries(0);
+    }
+    if !global.json && !global.compact {
+        eprintln!(
+            ":: {}",
+            format!("Probing {} mirrors ({} samples each)...", mirrors.len(), samples).cyan().bold()
+        );
+    }
+
+    let mut ranked: Vec<(&Mirror, Ranking)> = Vec::new();
+    let mut failed: Vec<(&Mirror, String)> = Vec::new();
+    for (mirror, result) in mirrors.iter().zip(probe_all(&mirrors, samples)) {
+        match result {
+            Ok(ranking) => ranked.push((mirror, ranking)),
+            Err(err) => failed.push((mirror, err.root_cause().to_string())),
+        }
+    }
+    ranked.sort_by(|a, b| b.1.bytes_per_sec.total_cmp(&a.1.bytes_per_sec));
+
+    if global.json {
+        let rows = ranked
+            .iter()
+            .map(|(mirror, ranking)| {
+                format!(
+                    "{{\"server\":\"{}\",\"latency_ms\":{},\"bytes_per_sec\":{},\"error\":null}}",
+                    json_escape(&mirror.server),
+                    ranking.latency.as_millis(),
+                    ranking.bytes_per_sec as u64
+                )
+            })
+            .chain(failed.iter().map(|(mirror, err)| {
+                format!(
+                    "{{\"server\":\"{}\",\"latency_ms\":null,\"bytes_per_sec\":null,\"error\":\"{}\"}}",
+                    json_escape(&mirror.server),
+                    json_escape(err)
+                )
+            }))
+            .collect::<Vec<_>>();
+        println!("[{}]", rows.join(","));
+    } else {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        println!(
+            "## Ranked by rustpack rankmirrors on {} ({} samples per mirror)",
+            history::format_local_time(now),
+            samples
+        );
+        for (mirror, ranking) in &ranked {
+            println!(
+                "## {}/s, {} ms",
+                format_bytes(ranking.bytes_per_sec as i64),
+                ranking.latency.as_millis()
+            );
+            println!("Server = {}", mirror.server);
+        }
+        for (mirror, err) in &failed {
+            println!("## unreachable: {}", err);
+            println!("#Server = {}", mirror.server);
+        }
+    }
+
+    if ranked.is_empty() {
+        bail!("none of the {} mirrors could be reached", mirrors.len());
+    }
+    Ok(())
+}
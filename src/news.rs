@@ -0,0 +1,159 @@
+use anyhow::{Result, bail};
+use colored::Colorize;
+use regex::Regex;
+use std::cmp::Reverse;
+
+use crate::cli::GlobalFlags;
+use crate::download;
+use crate::history;
+use crate::utils;
+
+const NEWS_FEED: &str = "https://archlinux.org/feeds/news/";
+/// How many of the newest feed items are considered at all.
+const MAX_ITEMS: usize = 5;
+
+struct NewsItem {
+    title: String,
+    link: String,
+    published: u64,
+}
+
+/// Extracts the feed's `<item>`s, newest first. Items without a parseable
+/// `pubDate` are skipped since they cannot be compared with the history.
+fn parse_feed(xml: &str) -> Vec<NewsItem> {
+    let item_re = Regex::new(r"(?s)<item>(.*?)</item>").unwrap();
+    let mut items: Vec<NewsItem> = item_re
+        .captures_iter(xml)
+        .filter_map(|caps| {
+            let body = &caps[1];
+            Some(NewsItem {
+                title: tag_text(body, "title")?,
+                link: tag_text(body, "link").unwrap_or_default(),
+                published: parse_rfc2822(&tag_text(body, "pubDate")?)?,
+            })
+        })
+        .collect();
+    items.sort_by_key(|item| Reverse(item.published));
+    items.truncate(MAX_ITEMS);
+    items
+}
+
+fn tag_text(body: &str, tag: &str) -> Option<String> {
+    let re = Regex::new(&format!(r"(?s)<{0}>(.*?)</{0}>", tag)).unwrap();
+    let raw = re.captures(body)?.get(1)?.as_str().trim();
+    let text = match raw.strip_prefix("<![CDATA[").and_then(|r| r.strip_suffix("]]>")) {
+        Some(inner) => inner.to_string(),
+        None => raw
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&"),
+    };
+    Some(text)
+}
+
+/// Parses an RSS date (`Tue, 08 Oct 2024 12:30:00 +0000`) into a UNIX timestamp.
+fn parse_rfc2822(input: &str) -> Option<u64> {
+    let mut fields: Vec<&str> = input.split_whitespace().collect();
+    if fields.first().is_some_and(|f| f.ends_with(',')) {
+        fields.remove(0);
+    }
+    let [day, month, year, time, zone] = fields.as_slice() else {
+        return None;
+    };
+    let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"]
+        .iter()
+        .position(|m| m.eq_ignore_ascii_case(month))?;
+    let mut clock = time.split(':').map(|p| p.parse::<i32>().ok());
+    let hour = clock.next()??;
+    let min = clock.next()??;
+    let sec = clock.next().unwrap_or(Some(0))?;
+    let offset = match *zone {
+        "GMT" | "UT" | "UTC" | "Z" => 0,
+        _ => {
+            let sign = match zone.chars().next()? {
+                '+' => 1,
+                '-' => -1,
+                _ => return None,
+            };
+            let digits = zone.get(1..5)?.parse::<i64>().ok()?;
+            sign * ((digits / 100) * 3600 + (digits % 100) * 60)
+        }
+    };
+
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year.parse::<i32>().ok()? - 1900;
+    tm.tm_mon = month as i32;
+    tm.tm_mday = day.parse().ok()?;
+    tm.tm_hour = hour;
+    tm.tm_min = min;
+    tm.tm_sec = sec;
+    let ts = unsafe { libc::timegm(&mut tm) } as i64 - offset;
+    u64::try_from(ts).ok()
+}
+
+/// Shows the news published since the last successful upgrade recorded in the
+/// history. A feed that cannot be fetched only warns. Under `--strict`, unread
+/// news must be acknowledged; returns `false` when the user declines.
+pub fn check_before_upgrade(global: &GlobalFlags) -> Result<bool> {
+    let xml = match download::fetch_url_text(NEWS_FEED) {
+        Ok(xml) => xml,
+        Err(e) => {
+            eprintln!("{} could not fetch Arch news: {}", "warning:".yellow().bold(), e);
+            return Ok(true);
+        }
+    };
+    let since = history::last_success(global, "upgrade").unwrap_or(0);
+    let unread: Vec<NewsItem> = parse_feed(&xml).into_iter().filter(|item| item.published > since).collect();
+    if unread.is_empty() {
+        return Ok(true);
+    }
+
+    if !global.json {
+        println!(":: {}", "Arch Linux news since your last upgrade:".cyan().bold());
+        for item in &unread {
+            let date = history::format_local_time(item.published);
+            println!(" {} {}", date.get(..10).unwrap_or(&date).dimmed(), item.title.bold());
+            if !item.link.is_empty() {
+                println!("     {}", item.link);
+            }
+        }
+    }
+    if !global.strict {
+        return Ok(true);
+    }
+    if global.noconfirm {
+        bail!("--strict: {} unread news item(s); read them and upgrade interactively, or pass --no-news", unread.len());
+    }
+    Ok(utils::confirm_with_default(":: Have you read the news above? [y/N] ", false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_feed_orders_newest_first() {
+        let xml = r#"<rss><channel><title>Arch Linux: Recent news updates</title>
+            <item><title>Old &amp; done</title><link>https://archlinux.org/news/old/</link>
+            <pubDate>Mon, 01 Jan 2024 00:00:00 +0000</pubDate></item>
+            <item><title>Manual intervention</title><link>https://archlinux.org/news/new/</link>
+            <pubDate>Tue, 02 Jan 2024 01:00:00 +0100</pubDate></item>
+            <item><title>No date</title></item>
+            </channel></rss>"#;
+        let items = parse_feed(xml);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "Manual intervention");
+        assert_eq!(items[0].published, 1_704_153_600);
+        assert_eq!(items[1].title, "Old & done");
+        assert_eq!(items[1].published, 1_704_067_200);
+    }
+
+    #[test]
+    fn test_parse_rfc2822_rejects_garbage() {
+        assert_eq!(parse_rfc2822("01 Jan 2024 00:00 GMT"), Some(1_704_067_200));
+        assert_eq!(parse_rfc2822("Mon, 01 Foo 2024 00:00:00 +0000"), None);
+        assert_eq!(parse_rfc2822("yesterday"), None);
+    }
+}
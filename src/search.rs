@@ -1,10 +1,16 @@
-use anyhow::Result;
-use alpm::{Package, PackageReason};
+use anyhow::{Result, bail};
+use alpm::{Package, PackageReason, PackageValidation, SigLevel};
 use colored::Colorize;
-use std::collections::VecDeque;
+use regex::{Regex, RegexBuilder};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
 
 use crate::alpm_ops;
+use crate::aur;
 use crate::cli::GlobalFlags;
+use crate::history;
+use crate::utils;
 
 fn format_list<T: std::fmt::Display>(items: Vec<T>) -> String {
     if items.is_empty() {
@@ -19,7 +25,7 @@ fn format_list<T: std::fmt::Display>(items: Vec<T>) -> String {
 }
 
 fn print_section_header(global: &GlobalFlags, title: &str, detail: Option<&str>) {
-    if global.compact {
+    if global.compact || global.quiet {
         return;
     }
     match detail {
@@ -29,13 +35,16 @@ fn print_section_header(global: &GlobalFlags, title: &str, detail: Option<&str>)
 }
 
 fn print_match_count(global: &GlobalFlags, count: usize) {
-    if global.compact {
+    if global.compact || global.quiet {
         return;
     }
     println!("\n{} {}", "Matches:".cyan().bold(), count.to_string().white().bold());
 }
 
-fn print_no_results() {
+fn print_no_results(global: &GlobalFlags) {
+    if global.quiet {
+        return;
+    }
     println!("{}", "No results found".yellow());
 }
 
@@ -65,16 +74,33 @@ fn print_pkg_row(
     arch: Option<&str>,
     size: Option<i64>,
 ) {
+    if global.quiet {
+        println!("{}", name);
+        return;
+    }
+    println!("{}", pkg_row_title(repo, name, version));
+    print_pkg_row_details(global, desc, arch, size);
+}
+
+fn pkg_row_title(repo: Option<&str>, name: &str, version: &str) -> String {
     let name_text = name.green().bold();
     let ver_text = version.yellow();
-    if let Some(r) = repo {
-        println!("{}/{} {}", r.blue().bold(), name_text, ver_text);
-    } else {
-        println!("{} {}", name_text, ver_text);
+    match repo {
+        Some(r) => format!("{}/{} {}", r.blue().bold(), name_text, ver_text),
+        None => format!("{} {}", name_text, ver_text),
     }
+}
+
+fn print_pkg_row_details(global: &GlobalFlags, desc: Option<&str>, arch: Option<&str>, size: Option<i64>) {
     if !global.compact {
         if let Some(d) = desc {
-            println!("    {}", d.dimmed());
+            match global.output_width() {
+                Some(width) => {
+                    let fitted = utils::truncate_with_ellipsis(d, width.saturating_sub(4).max(10));
+                    println!("    {}", fitted.dimmed());
+                }
+                None => println!("    {}", d.dimmed()),
+            }
         }
     }
     if global.verbose {
@@ -93,7 +119,46 @@ fn print_pkg_row(
     }
 }
 
-fn pkg_info_json(pkg: &Package, is_local: bool) -> String {
+fn validation_names(pkg: &Package) -> Vec<&'static str> {
+    let validation = pkg.validation();
+    let mut names = Vec::new();
+    if validation.contains(PackageValidation::MD5SUM) {
+        names.push("MD5 Sum");
+    }
+    if validation.contains(PackageValidation::SHA256SUM) {
+        names.push("SHA-256 Sum");
+    }
+    if validation.contains(PackageValidation::SIGNATURE) {
+        names.push("Signature");
+    }
+    if names.is_empty() {
+        names.push("None");
+    }
+    names
+}
+
+/// Backup files of `pkg` with their state under `root`: the file's md5 is
+/// compared to the one recorded at install time, like `pacman -Qii`.
+fn backup_states(root: &str, pkg: &Package) -> Vec<(String, &'static str)> {
+    pkg.backup()
+        .iter()
+        .map(|backup| {
+            let path = Path::new(root).join(backup.name());
+            let state = if !path.exists() {
+                "MISSING"
+            } else {
+                match alpm::compute_md5sum(path.to_string_lossy().as_ref()) {
+                    Ok(sum) if sum == backup.hash() => "UNMODIFIED",
+                    Ok(_) => "MODIFIED",
+                    Err(_) => "UNREADABLE",
+                }
+            };
+            (path.to_string_lossy().to_string(), state)
+        })
+        .collect()
+}
+
+fn pkg_info_json(pkg: &Package, is_local: bool, level: u8, root: &str) -> String {
     let db_name = pkg.db().map(|db| db.name()).unwrap_or("unknown");
     let mut out = String::new();
     out.push_str("{");
@@ -110,10 +175,31 @@ fn pkg_info_json(pkg: &Package, is_local: bool) -> String {
     out.push_str(format!(",\"groups\":[{}]", json_array(groups)).as_str());
     out.push_str(format!(",\"depends\":[{}]", json_array(depends)).as_str());
     out.push_str(format!(",\"optdepends\":[{}]", json_array(optdepends)).as_str());
+    let provides = pkg.provides().iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let conflicts = pkg.conflicts().iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let replaces = pkg.replaces().iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let required_by = pkg.required_by().iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let optional_for = pkg.optional_for().iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    out.push_str(format!(",\"provides\":[{}]", json_array(provides)).as_str());
+    out.push_str(format!(",\"conflicts\":[{}]", json_array(conflicts)).as_str());
+    out.push_str(format!(",\"replaces\":[{}]", json_array(replaces)).as_str());
+    out.push_str(format!(",\"required_by\":[{}]", json_array(required_by)).as_str());
+    out.push_str(format!(",\"optional_for\":[{}]", json_array(optional_for)).as_str());
+    out.push_str(format!(",\"packager\":\"{}\"", json_escape(pkg.packager().unwrap_or("Unknown Packager"))).as_str());
+    out.push_str(format!(",\"build_date\":{}", pkg.build_date()).as_str());
     if is_local {
         out.push_str(format!(",\"install_reason\":\"{:?}\"", pkg.reason()).as_str());
         out.push_str(format!(",\"install_date\":{}", pkg.install_date().unwrap_or(0)).as_str());
         out.push_str(format!(",\"installed_size\":{}", pkg.isize()).as_str());
+        let validation = validation_names(pkg).into_iter().map(|v| v.to_string()).collect();
+        out.push_str(format!(",\"validated_by\":[{}]", json_array(validation)).as_str());
+        if level > 1 {
+            let backups = backup_states(root, pkg)
+                .iter()
+                .map(|(path, state)| format!("{{\"path\":\"{}\",\"state\":\"{}\"}}", json_escape(path), state))
+                .collect::<Vec<_>>();
+            out.push_str(format!(",\"backup\":[{}]", backups.join(",")).as_str());
+        }
     } else {
         out.push_str(format!(",\"repository\":\"{}\"", json_escape(db_name)).as_str());
         out.push_str(format!(",\"download_size\":{}", pkg.download_size()).as_str());
@@ -123,12 +209,47 @@ fn pkg_info_json(pkg: &Package, is_local: bool) -> String {
     out
 }
 
-fn print_pkg_info(pkg: &Package, is_local: bool, global: &GlobalFlags) {
+/// Extracts the package name from an optdepend entry such as `foo>=1.0: description`.
+fn optdep_name(entry: &str) -> &str {
+    let name = entry.split(':').next().unwrap_or(entry).trim();
+    name.split(|c| c == '=' || c == '<' || c == '>')
+        .next()
+        .unwrap_or(name)
+}
+
+fn optdepends_annotated(pkg: &Package, is_local: bool) -> Vec<String> {
+    let localdb = if is_local { pkg.db() } else { None };
+    pkg.optdepends()
+        .iter()
+        .map(|dep| {
+            let entry = dep.to_string();
+            let installed = localdb
+                .map(|db| db.pkg(optdep_name(&entry)).is_ok())
+                .unwrap_or(false);
+            if installed {
+                format!("{} [installed]", entry)
+            } else {
+                entry
+            }
+        })
+        .collect()
+}
+
+/// `-Si`/`-Qi`; a doubled `-Sii`/`-Qii` (`level` 2) adds backup files and
+/// human-readable sizes.
+fn print_pkg_info(pkg: &Package, is_local: bool, level: u8, root: &str, global: &GlobalFlags) {
     if global.json {
-        println!("{}", pkg_info_json(pkg, is_local));
+        println!("{}", pkg_info_json(pkg, is_local, level, root));
         return;
     }
     let db_name = pkg.db().map(|db| db.name()).unwrap_or("unknown");
+    let size = |bytes: i64| {
+        if level > 1 {
+            format!("{} ({} bytes)", crate::install::format_bytes(bytes), bytes)
+        } else {
+            bytes.to_string()
+        }
+    };
     println!("Name            : {}", pkg.name());
     println!("Version         : {}", pkg.version());
     println!("Description     : {}", pkg.desc().unwrap_or("None"));
@@ -137,45 +258,246 @@ fn print_pkg_info(pkg: &Package, is_local: bool, global: &GlobalFlags) {
     println!("Licenses        : {}", format_list(pkg.licenses().iter().collect()));
     println!("Groups          : {}", format_list(pkg.groups().iter().collect()));
     println!("Depends On      : {}", format_list(pkg.depends().iter().collect()));
-    println!("Optional Deps   : {}", format_list(pkg.optdepends().iter().collect()));
+    println!("Optional Deps   : {}", format_list(optdepends_annotated(pkg, is_local)));
+    println!("Provides        : {}", format_list(pkg.provides().iter().collect()));
+    println!("Conflicts With  : {}", format_list(pkg.conflicts().iter().collect()));
+    println!("Replaces        : {}", format_list(pkg.replaces().iter().collect()));
+    println!("Required By     : {}", format_list(pkg.required_by().iter().collect()));
+    println!("Optional For    : {}", format_list(pkg.optional_for().iter().collect()));
+    println!("Packager        : {}", pkg.packager().unwrap_or("Unknown Packager"));
+    println!("Build Date      : {}", history::format_local_time(pkg.build_date().max(0) as u64));
     if is_local {
         println!("Install Reason  : {:?}", pkg.reason());
-        println!("Install Date    : {}", pkg.install_date().unwrap_or(0));
-        println!("Installed Size  : {}", pkg.isize());
+        let install_date = pkg
+            .install_date()
+            .map(|date| history::format_local_time(date.max(0) as u64))
+            .unwrap_or_else(|| "None".to_string());
+        println!("Install Date    : {}", install_date);
+        println!("Installed Size  : {}", size(pkg.isize()));
+        println!("Validated By    : {}", format_list(validation_names(pkg)));
+        if level > 1 {
+            let backups = backup_states(root, pkg);
+            if backups.is_empty() {
+                println!("Backup Files    : None");
+            } else {
+                println!("Backup Files    :");
+                for (path, state) in backups {
+                    println!("{}\t{}", state, path);
+                }
+            }
+        }
     } else {
         println!("Repository      : {}", db_name);
-        println!("Download Size   : {}", pkg.download_size());
-        println!("Installed Size  : {}", pkg.isize());
+        println!("Download Size   : {}", size(pkg.download_size()));
+        println!("Installed Size  : {}", size(pkg.isize()));
+    }
+}
+
+/// One `-Ss`/`-Qs` match, copied out of the database so hits from every
+/// repository can be ranked together before printing.
+struct SearchHit {
+    repo: Option<String>,
+    name: String,
+    version: String,
+    desc: Option<String>,
+    arch: Option<String>,
+    isize: i64,
+    /// Version installed locally, if any.
+    installed: Option<String>,
+    /// AUR votes; only set for `--aur-search` results.
+    votes: Option<u32>,
+    relevance: u8,
+}
+
+impl SearchHit {
+    fn new(repo: Option<&str>, pkg: &Package, installed: Option<String>, queries: &[String]) -> Self {
+        SearchHit {
+            repo: repo.map(|r| r.to_string()),
+            name: pkg.name().to_string(),
+            version: pkg.version().to_string(),
+            desc: pkg.desc().map(|d| d.to_string()),
+            arch: pkg.arch().map(|a| a.to_string()),
+            isize: pkg.isize(),
+            installed,
+            votes: None,
+            relevance: utils::search_relevance(pkg.name(), queries),
+        }
+    }
+    
+    fn aur(pkg: aur::AurPackage, installed: Option<String>, queries: &[String]) -> Self {
+        let relevance = utils::search_relevance(&pkg.name, queries);
+        let mut desc = pkg.description;
+        if pkg.out_of_date.is_some() {
+            desc = Some(format!("(out of date) {}", desc.unwrap_or_default()));
+        }
+        SearchHit {
+            repo: Some("aur".to_string()),
+            name: pkg.name,
+            version: pkg.version,
+            desc,
+            arch: None,
+            isize: 0,
+            installed,
+            votes: Some(pkg.num_votes),
+            relevance,
+        }
     }
 }
 
-pub fn search_repos(global: &GlobalFlags, queries: &[String]) -> Result<()> {
+fn search_result_json(hit: &SearchHit) -> String {
+    format!(
+        "{{\"repo\":\"{}\",\"name\":\"{}\",\"version\":\"{}\",\"description\":\"{}\",\"installed\":{}{}}}",
+        json_escape(hit.repo.as_deref().unwrap_or("local")),
+        json_escape(&hit.name),
+        json_escape(&hit.version),
+        json_escape(hit.desc.as_deref().unwrap_or("")),
+        hit.installed.is_some(),
+        hit.votes.map(|v| format!(",\"votes\":{}", v)).unwrap_or_default()
+    )
+}
+
+/// Orders hits by relevance (repository order breaks ties), prints at most
+/// `--limit` of them and reports the total.
+fn print_search_hits(global: &GlobalFlags, mut hits: Vec<SearchHit>) {
+    hits.sort_by_key(|hit| hit.relevance);
+    let total = hits.len();
+    hits.truncate(global.limit.unwrap_or(total));
+    
+    if global.json {
+        let rows = hits.iter().map(search_result_json).collect::<Vec<_>>();
+        println!("[{}]", rows.join(","));
+        return;
+    }
+    if total == 0 {
+        print_no_results(global);
+        return;
+    }
+    for hit in &hits {
+        if global.quiet {
+            println!("{}", hit.name);
+            continue;
+        }
+        let mut title = pkg_row_title(hit.repo.as_deref(), &hit.name, &hit.version);
+        if let Some(votes) = hit.votes {
+            title = format!("{} {}", title, format!("(+{})", votes).cyan());
+        }
+        // Installed packages are only worth flagging among repository results
+        match hit.installed.as_deref() {
+            Some(local) if hit.repo.is_some() && local != hit.version => {
+                println!("{} {}", title, format!("[installed: {}]", local).green())
+            }
+            Some(_) if hit.repo.is_some() => println!("{} {}", title, "[installed]".green()),
+            _ => println!("{}", title),
+        }
+        print_pkg_row_details(global, hit.desc.as_deref(), hit.arch.as_deref(), Some(hit.isize));
+    }
+    print_match_count(global, total);
+    if hits.len() < total && !global.quiet {
+        println!("{}", format!("(showing the first {}; raise or drop --limit to see all)", hits.len()).dimmed());
+    }
+}
+
+/// `--name-only` patterns. libalpm matches names and descriptions alike, so
+/// its results are narrowed to names matching every query, case-insensitively
+/// like libalpm; a query that is not a valid regex is matched literally.
+fn name_patterns(queries: &[String]) -> Vec<Regex> {
+    queries
+        .iter()
+        .filter_map(|query| {
+            RegexBuilder::new(query)
+                .case_insensitive(true)
+                .build()
+                .or_else(|_| RegexBuilder::new(&regex::escape(query)).case_insensitive(true).build())
+                .ok()
+        })
+        .collect()
+}
+
+fn name_matches(patterns: &[Regex], name: &str) -> bool {
+    patterns.iter().all(|re| re.is_match(name))
+}
+
+/// `-Ss`; with `aur` the AUR RPC is searched as well and its hits, labelled
+/// `aur/`, are ranked together with the repository ones.
+pub fn search_repos(global: &GlobalFlags, queries: &[String], aur: bool) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
+    let localdb = handle.localdb();
+    let local_version = |name: &str| localdb.pkg(name).ok().map(|pkg| pkg.version().to_string());
     let query_refs: Vec<&str> = queries.iter().map(|s| s.as_str()).collect();
+    let patterns = name_patterns(queries);
     
-    let mut found = false;
-    let mut count = 0usize;
-    print_section_header(global, "Searching repositories for:", Some(&queries.join(" ")));
+    let mut hits = Vec::new();
     for db in handle.syncdbs().iter() {
-        let results = db.search(query_refs.iter())?;
-        for pkg in results.iter() {
+        if global.exact {
+            // Whole-name matches need no scan of the database
+            for query in queries {
+                if let Ok(pkg) = db.pkg(query.as_str()) {
+                    hits.push(SearchHit::new(Some(db.name()), pkg, local_version(pkg.name()), queries));
+                }
+            }
+            continue;
+        }
+        for pkg in db.search(query_refs.iter())?.iter() {
+            if global.name_only && !name_matches(&patterns, pkg.name()) {
+                continue;
+            }
             let repo = pkg.db().map(|d| d.name()).unwrap_or(db.name());
-            count += 1;
+            hits.push(SearchHit::new(Some(repo), pkg, local_version(pkg.name()), queries));
+        }
+    }
+    if aur {
+        // Repository results are still worth showing when the AUR is unreachable
+        match aur::search(queries) {
+            Ok(found) => hits.extend(
+                found
+                    .into_iter()
+                    .filter(|pkg| !global.exact || queries.contains(&pkg.name))
+                    .filter(|pkg| !global.name_only || name_matches(&patterns, &pkg.name))
+                    .map(|pkg| {
+                        let installed = local_version(&pkg.name);
+                        SearchHit::aur(pkg, installed, queries)
+                    }),
+            ),
+            Err(err) => eprintln!("{} AUR search failed: {:#}", "warning:".yellow().bold(), err),
+        }
+    }
+    
+    if !global.json {
+        print_section_header(global, "Searching repositories for:", Some(&queries.join(" ")));
+    }
+    print_search_hits(global, hits);
+    
+    Ok(())
+}
+
+pub fn list_repo_packages(global: &GlobalFlags, repos: &[String]) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    for repo in repos {
+        if !handle.syncdbs().iter().any(|db| db.name() == repo) {
+            return Err(anyhow::anyhow!("repository '{}' was not found", repo));
+        }
+    }
+    
+    let mut count = 0usize;
+    for db in handle.syncdbs().iter() {
+        if !repos.is_empty() && !repos.iter().any(|r| r == db.name()) {
+            continue;
+        }
+        for pkg in db.pkgs().iter() {
             print_pkg_row(
                 global,
-                Some(repo),
+                Some(db.name()),
                 pkg.name(),
                 &pkg.version().to_string(),
-                pkg.desc(),
+                if global.verbose { pkg.desc() } else { None },
                 pkg.arch(),
                 Some(pkg.isize()),
             );
-            found = true;
+            count += 1;
         }
     }
-    
-    if !found {
-        print_no_results();
+    if count == 0 {
+        print_no_results(global);
     } else {
         print_match_count(global, count);
     }
@@ -183,6 +505,110 @@ pub fn search_repos(global: &GlobalFlags, queries: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// `-Qg [group...]`: installed packages grouped by the groups they belong to.
+pub fn query_groups(global: &GlobalFlags, groups: &[String]) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let mut members: BTreeMap<String, Vec<&Package>> = BTreeMap::new();
+    for pkg in handle.localdb().pkgs().iter() {
+        for group in pkg.groups().iter() {
+            if groups.is_empty() || groups.iter().any(|g| g == group) {
+                members.entry(group.to_string()).or_default().push(pkg);
+            }
+        }
+    }
+    for group in groups {
+        if !members.contains_key(group) {
+            return Err(anyhow::anyhow!("group '{}' was not found", group));
+        }
+    }
+    
+    for (group, pkgs) in &members {
+        print_group_members(global, group, None, pkgs);
+    }
+    if members.is_empty() && !global.compact {
+        print_no_results(global);
+    }
+    Ok(())
+}
+
+/// `-Sg <group...>`: members of a group across all sync repositories.
+pub fn list_sync_group(global: &GlobalFlags, groups: &[String]) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    for group in groups {
+        let mut found = false;
+        for db in handle.syncdbs().iter() {
+            if let Ok(grp) = db.group(group.as_str()) {
+                found = true;
+                let pkgs: Vec<&Package> = grp.packages().iter().collect();
+                print_group_members(global, group, Some(db.name()), &pkgs);
+            }
+        }
+        if !found {
+            return Err(anyhow::anyhow!(
+                "group '{}' was not found in any configured repository",
+                group
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn print_group_members(global: &GlobalFlags, group: &str, repo: Option<&str>, pkgs: &[&Package]) {
+    if global.quiet {
+        for pkg in pkgs {
+            println!("{}", pkg.name());
+        }
+        return;
+    }
+    if global.compact {
+        for pkg in pkgs {
+            println!("{}/{}", group, pkg.name());
+        }
+        return;
+    }
+    let detail = repo.map(|r| format!("({})", r));
+    print_section_header(global, &format!("Group {}", group), detail.as_deref());
+    for pkg in pkgs {
+        print_pkg_row(
+            global,
+            repo,
+            pkg.name(),
+            &pkg.version().to_string(),
+            if global.verbose { pkg.desc() } else { None },
+            pkg.arch(),
+            Some(pkg.isize()),
+        );
+    }
+    println!();
+}
+
+pub fn list_repos(global: &GlobalFlags) -> Result<()> {
+    let config = alpm_ops::effective_config(global)?;
+    if global.json {
+        let rows = config
+            .repositories
+            .iter()
+            .map(|repo| {
+                format!(
+                    "{{\"name\":\"{}\",\"siglevel\":\"{}\",\"servers\":[{}]}}",
+                    json_escape(&repo.name),
+                    json_escape(&repo.sig_level),
+                    json_array(alpm_ops::expanded_servers(&config, repo))
+                )
+            })
+            .collect::<Vec<_>>();
+        println!("[{}]", rows.join(","));
+        return Ok(());
+    }
+    for repo in &config.repositories {
+        println!("{} {}", repo.name.blue().bold(), format!("SigLevel={}", repo.sig_level).dimmed());
+        for url in alpm_ops::expanded_servers(&config, repo) {
+            println!("  {}", url);
+        }
+    }
+    Ok(())
+}
+
 pub fn list_installed(global: &GlobalFlags) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
     let db = handle.localdb();
@@ -211,58 +637,72 @@ pub fn search_installed(global: &GlobalFlags, queries: &[String]) -> Result<()>
     let db = handle.localdb();
     let query_refs: Vec<&str> = queries.iter().map(|s| s.as_str()).collect();
     
-    let results = db.search(query_refs.iter())?;
-    if results.is_empty() {
-        print_no_results();
-        return Ok(());
-    }
-    print_section_header(global, "Searching installed packages for:", Some(&queries.join(" ")));
+    let patterns = name_patterns(queries);
     
-    for pkg in results.iter() {
-        print_pkg_row(
-            global,
-            None,
-            pkg.name(),
-            &pkg.version().to_string(),
-            pkg.desc(),
-            pkg.arch(),
-            Some(pkg.isize()),
-        );
+    let hits: Vec<SearchHit> = if global.exact {
+        queries
+            .iter()
+            .filter_map(|query| db.pkg(query.as_str()).ok())
+            .map(|pkg| SearchHit::new(None, pkg, Some(pkg.version().to_string()), queries))
+            .collect()
+    } else {
+        db.search(query_refs.iter())?
+            .iter()
+            .filter(|pkg| !global.name_only || name_matches(&patterns, pkg.name()))
+            .map(|pkg| SearchHit::new(None, pkg, Some(pkg.version().to_string()), queries))
+            .collect()
+    };
+    if !hits.is_empty() && !global.json {
+        print_section_header(global, "Searching installed packages for:", Some(&queries.join(" ")));
     }
-    print_match_count(global, results.len());
+    print_search_hits(global, hits);
     
     Ok(())
 }
 
-pub fn show_package_info(global: &GlobalFlags, package_name: &str) -> Result<()> {
+pub fn show_package_info(global: &GlobalFlags, package_name: &str, level: u8) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
-    let pkg = alpm_ops::find_local_pkg(&handle, package_name)
-        .map_err(|_| anyhow::anyhow!("error: package '{}' was not found", package_name))?;
-    print_pkg_info(pkg, true, global);
+    let pkg = alpm_ops::find_local_pkg(&handle, package_name)?;
+    print_pkg_info(pkg, true, level, handle.root(), global);
     Ok(())
 }
 
-pub fn show_sync_package_info(global: &GlobalFlags, package_name: &str) -> Result<()> {
+pub fn show_sync_package_info(global: &GlobalFlags, package_name: &str, level: u8) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let pkg = alpm_ops::find_sync_pkg(&handle, package_name)?;
+    print_pkg_info(pkg, false, level, handle.root(), global);
+    Ok(())
+}
+
+pub fn show_sync_package_infos(global: &GlobalFlags, package_names: &[String], level: u8) -> Result<()> {
+    if !global.json {
+        for pkg in package_names {
+            show_sync_package_info(global, pkg, level)?;
+        }
+        return Ok(());
+    }
     let handle = alpm_ops::init_handle(global)?;
-    let pkg = alpm_ops::find_sync_pkg(&handle, package_name)
-        .map_err(|_| anyhow::anyhow!("error: package '{}' was not found", package_name))?;
-    print_pkg_info(pkg, false, global);
+    let mut items = Vec::new();
+    for package_name in package_names {
+        let pkg = alpm_ops::find_sync_pkg(&handle, package_name)?;
+        items.push(pkg_info_json(pkg, false, level, handle.root()));
+    }
+    println!("[{}]", items.join(","));
     Ok(())
 }
 
-pub fn show_local_package_infos(global: &GlobalFlags, package_names: &[String]) -> Result<()> {
+pub fn show_local_package_infos(global: &GlobalFlags, package_names: &[String], level: u8) -> Result<()> {
     if !global.json {
         for pkg in package_names {
-            show_package_info(global, pkg)?;
+            show_package_info(global, pkg, level)?;
         }
         return Ok(());
     }
     let handle = alpm_ops::init_handle(global)?;
     let mut items = Vec::new();
     for package_name in package_names {
-        let pkg = alpm_ops::find_local_pkg(&handle, package_name)
-            .map_err(|_| anyhow::anyhow!("error: package '{}' was not found", package_name))?;
-        items.push(pkg_info_json(pkg, true));
+        let pkg = alpm_ops::find_local_pkg(&handle, package_name)?;
+        items.push(pkg_info_json(pkg, true, level, handle.root()));
     }
     println!("[{}]", items.join(","));
     Ok(())
@@ -289,51 +729,301 @@ pub fn query_packages(global: &GlobalFlags, packages: &[String]) -> Result<()> {
     Ok(())
 }
 
-pub fn list_package_files(global: &GlobalFlags, packages: &[String]) -> Result<()> {
+enum FileProblem {
+    Missing,
+    Size { expected: i64, actual: u64 },
+    Mode { expected: u32, actual: u32 },
+}
+
+impl FileProblem {
+    fn describe(&self) -> String {
+        match self {
+            FileProblem::Missing => "missing".to_string(),
+            FileProblem::Size { expected, actual } => format!("size {} != {}", actual, expected),
+            FileProblem::Mode { expected, actual } => format!("mode {:o} != {:o}", actual, expected),
+        }
+    }
+}
+
+/// Stats every file recorded for `pkg` under `root`. Symlinks count as present
+/// even when dangling. With `thorough` (`-Qkk`), sizes and modes are compared
+/// too, but only when the database recorded them (non-zero).
+fn file_problems(root: &str, pkg: &Package, thorough: bool) -> Vec<(String, FileProblem)> {
+    use std::os::unix::fs::PermissionsExt;
+    
+    let mut problems = Vec::new();
+    for file in pkg.files().files() {
+        let name = String::from_utf8_lossy(file.name()).to_string();
+        let path = Path::new(root).join(&name);
+        let display = path.to_string_lossy().to_string();
+        let meta = match fs::symlink_metadata(&path) {
+            Ok(meta) => meta,
+            Err(_) => {
+                problems.push((display, FileProblem::Missing));
+                continue;
+            }
+        };
+        if !thorough {
+            continue;
+        }
+        if meta.file_type().is_file() && file.size() > 0 && meta.len() != file.size() as u64 {
+            problems.push((display.clone(), FileProblem::Size { expected: file.size(), actual: meta.len() }));
+        }
+        let expected_mode = file.mode() & 0o7777;
+        let actual_mode = meta.permissions().mode() & 0o7777;
+        if !meta.file_type().is_symlink() && file.mode() != 0 && expected_mode != actual_mode {
+            problems.push((display, FileProblem::Mode { expected: expected_mode, actual: actual_mode }));
+        }
+    }
+    problems
+}
+
+fn missing_files(root: &str, pkg: &Package) -> Vec<String> {
+    file_problems(root, pkg, false)
+        .into_iter()
+        .filter(|(_, problem)| matches!(problem, FileProblem::Missing))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// `-Qk`/`-Qkk`: checks installed files against the local database. With no
+/// targets every installed package is checked.
+pub fn check_package_files(global: &GlobalFlags, packages: &[String], level: u8) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
     let db = handle.localdb();
+    let mut pkgs = Vec::new();
+    if packages.is_empty() {
+        pkgs.extend(db.pkgs().iter());
+    } else {
+        for pkg_name in packages {
+            pkgs.push(
+                alpm_ops::find_local_pkg(&handle, pkg_name)?,
+            );
+        }
+    }
+    
+    let mut broken_pkgs = 0usize;
+    for pkg in pkgs {
+        let problems = file_problems(handle.root(), pkg, level > 1);
+        for (path, problem) in &problems {
+            println!(
+                "{} {}: {} ({})",
+                "warning:".yellow().bold(),
+                pkg.name(),
+                path,
+                problem.describe()
+            );
+        }
+        let missing = problems.iter().filter(|(_, p)| matches!(p, FileProblem::Missing)).count();
+        let altered = problems.len() - missing;
+        let total = pkg.files().files().len();
+        if level > 1 {
+            println!(
+                "{}: {} total files, {} missing files, {} altered files",
+                pkg.name().green().bold(),
+                total,
+                missing,
+                altered
+            );
+        } else {
+            println!("{}: {} total files, {} missing files", pkg.name().green().bold(), total, missing);
+        }
+        if !problems.is_empty() {
+            broken_pkgs += 1;
+        }
+    }
+    
+    if broken_pkgs > 0 {
+        bail!("{} package(s) have missing or altered files", broken_pkgs);
+    }
+    Ok(())
+}
+
+pub fn list_missing_files(global: &GlobalFlags, packages: &[String]) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let mut total_missing = 0usize;
     
     for pkg_name in packages {
-        let pkg = db.pkg(pkg_name.as_str())
-            .map_err(|_| anyhow::anyhow!("error: package '{}' was not found", pkg_name))?;
-        if !global.compact {
-            println!("\n{} {}", "Files for".cyan().bold(), pkg.name().green().bold());
-        }
-        let files = pkg.files();
-        let mut count = 0usize;
-        for file in files.files() {
-            let name = String::from_utf8_lossy(file.name()).to_string();
-            if global.compact {
-                println!("{} {}", pkg.name().green().bold(), name);
+        let pkg = alpm_ops::find_local_pkg(&handle, pkg_name)?;
+        let missing = missing_files(handle.root(), pkg);
+        if !global.compact && !global.quiet {
+            println!("\n{} {}", "Missing files for".cyan().bold(), pkg.name().green().bold());
+        }
+        for path in &missing {
+            if global.quiet {
+                println!("{}", path);
+            } else if global.compact {
+                println!("{} {}", pkg.name().green().bold(), path);
             } else {
-                println!("  {}", name.dimmed());
+                println!("  {}", path.red());
             }
-            count += 1;
         }
+        if !global.compact && !global.quiet {
+            println!("{} {}", "Missing count:".cyan().bold(), missing.len());
+        }
+        total_missing += missing.len();
+    }
+    
+    if total_missing > 0 {
+        bail!("{} file(s) missing from the queried packages", total_missing);
+    }
+    Ok(())
+}
+
+pub fn show_changelog(global: &GlobalFlags, packages: &[String]) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    
+    for pkg_name in packages {
+        let pkg = alpm_ops::find_local_pkg(&handle, pkg_name)?;
         if !global.compact {
-            println!("{} {}", "File count:".cyan().bold(), count);
+            println!("{} {}", "Changelog for".cyan().bold(), pkg.name().green().bold());
+        }
+        match pkg.changelog() {
+            Ok(mut changelog) => {
+                let stdout = std::io::stdout();
+                std::io::copy(&mut changelog, &mut stdout.lock())?;
+            }
+            Err(_) => println!(" {} no changelog available for {}", "note:".yellow(), pkg.name()),
         }
     }
+    Ok(())
+}
+
+pub fn list_package_files(global: &GlobalFlags, packages: &[String]) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    
+    for pkg_name in packages {
+        let pkg = alpm_ops::find_local_pkg(&handle, pkg_name)?;
+        print_file_list(global, pkg);
+    }
     
     Ok(())
 }
 
+fn print_file_list(global: &GlobalFlags, pkg: &Package) {
+    if !global.compact && !global.quiet {
+        println!("\n{} {}", "Files for".cyan().bold(), pkg.name().green().bold());
+    }
+    let files = pkg.files();
+    let mut count = 0usize;
+    for file in files.files() {
+        let name = String::from_utf8_lossy(file.name()).to_string();
+        if global.quiet {
+            println!("{}", name);
+        } else if global.compact {
+            println!("{} {}", pkg.name().green().bold(), name);
+        } else {
+            println!("  {}", name.dimmed());
+        }
+        count += 1;
+    }
+    if !global.compact && !global.quiet {
+        println!("{} {}", "File count:".cyan().bold(), count);
+    }
+}
+
+/// `-Qp`: reads package files instead of the local database, with `-l`
+/// listing their contents. Signatures are not checked since nothing is
+/// installed.
+pub fn query_package_files(global: &GlobalFlags, files: &[String], list_files: bool, level: u8) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let mut items = Vec::new();
+    
+    for file in files {
+        if !Path::new(file).is_file() {
+            bail!("package file '{}' was not found", file);
+        }
+        let pkg = handle
+            .pkg_load(file.as_str(), true, SigLevel::NONE)
+            .map_err(|err| anyhow::anyhow!("could not load '{}': {}", file, err))?;
+        if list_files {
+            print_file_list(global, &pkg);
+        } else if global.json {
+            items.push(pkg_info_json(&pkg, true, level, handle.root()));
+        } else {
+            print_pkg_info(&pkg, true, level, handle.root(), global);
+        }
+    }
+    if !list_files && global.json {
+        println!("[{}]", items.join(","));
+    }
+    
+    Ok(())
+}
+
+pub fn sync_package_names(handle: &alpm::Alpm) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for db in handle.syncdbs().iter() {
+        for pkg in db.pkgs().iter() {
+            names.insert(pkg.name().to_string());
+        }
+    }
+    names
+}
+
 pub fn list_manual_packages(global: &GlobalFlags) -> Result<()> {
+    list_packages_by_origin(global, false)
+}
+
+pub fn list_native_packages(global: &GlobalFlags) -> Result<()> {
+    list_packages_by_origin(global, true)
+}
+
+fn list_packages_by_origin(global: &GlobalFlags, native: bool) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
     let localdb = handle.localdb();
-    let syncdbs = handle.syncdbs();
+    let native_names = sync_package_names(&handle);
     
-    print_section_header(global, "Foreign packages", None);
+    print_section_header(
+        global,
+        if native { "Native packages" } else { "Foreign packages" },
+        None,
+    );
     let mut count = 0usize;
     for pkg in localdb.pkgs().iter() {
-        let mut found = false;
-        for db in syncdbs.iter() {
-            if db.pkg(pkg.name()).is_ok() {
-                found = true;
-                break;
-            }
+        if native_names.contains(pkg.name()) == native {
+            print_pkg_row(
+                global,
+                None,
+                pkg.name(),
+                &pkg.version().to_string(),
+                if global.verbose { pkg.desc() } else { None },
+                pkg.arch(),
+                Some(pkg.isize()),
+            );
+            count += 1;
         }
-        if !found {
+    }
+    if count == 0 {
+        print_no_results(global);
+    } else {
+        print_match_count(global, count);
+    }
+    
+    Ok(())
+}
+
+/// Dependencies that nothing requires or optionally uses any more.
+pub fn orphan_names(handle: &alpm::Alpm) -> Vec<String> {
+    handle
+        .localdb()
+        .pkgs()
+        .iter()
+        .filter(|pkg| {
+            pkg.reason() == PackageReason::Depend && pkg.required_by().is_empty() && pkg.optional_for().is_empty()
+        })
+        .map(|pkg| pkg.name().to_string())
+        .collect()
+}
+
+pub fn list_orphans(global: &GlobalFlags) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let localdb = handle.localdb();
+    
+    print_section_header(global, "Orphaned dependencies", None);
+    let mut count = 0usize;
+    for name in orphan_names(&handle) {
+        if let Ok(pkg) = localdb.pkg(name.as_str()) {
             print_pkg_row(
                 global,
                 None,
@@ -347,7 +1037,9 @@ pub fn list_manual_packages(global: &GlobalFlags) -> Result<()> {
         }
     }
     if count == 0 {
-        print_no_results();
+        if !global.compact {
+            print_no_results(global);
+        }
     } else {
         print_match_count(global, count);
     }
@@ -355,6 +1047,46 @@ pub fn list_manual_packages(global: &GlobalFlags) -> Result<()> {
     Ok(())
 }
 
+pub fn list_upgradable(global: &GlobalFlags) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let mut upgrades = Vec::new();
+    for pkg in handle.localdb().pkgs().iter() {
+        if let Some(newer) = pkg.sync_new_version(handle.syncdbs()) {
+            upgrades.push((pkg.name().to_string(), pkg.version().to_string(), newer.version().to_string()));
+        }
+    }
+    
+    if global.json {
+        let rows = upgrades
+            .iter()
+            .map(|(name, old, new)| {
+                format!(
+                    "{{\"name\":\"{}\",\"version\":\"{}\",\"new_version\":\"{}\"}}",
+                    json_escape(name),
+                    json_escape(old),
+                    json_escape(new)
+                )
+            })
+            .collect::<Vec<_>>();
+        println!("[{}]", rows.join(","));
+        return Ok(());
+    }
+    
+    for (name, old, new) in &upgrades {
+        if global.quiet {
+            println!("{}", name);
+        } else if global.compact {
+            println!("{} {}", name, new);
+        } else {
+            println!("{} {} -> {}", name.green().bold(), old.yellow(), new.green());
+        }
+    }
+    if upgrades.is_empty() && !global.compact {
+        print_no_results(global);
+    }
+    Ok(())
+}
+
 pub fn list_explicit_packages(global: &GlobalFlags) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
     let localdb = handle.localdb();
@@ -393,7 +1125,7 @@ pub fn list_explicit_packages(global: &GlobalFlags) -> Result<()> {
         }
     }
     if count == 0 {
-        print_no_results();
+        print_no_results(global);
     } else {
         print_match_count(global, count);
     }
@@ -407,9 +1139,7 @@ pub fn query_explicit_packages(global: &GlobalFlags, packages: &[String]) -> Res
     if global.json {
         let mut rows = Vec::new();
         for pkg_name in packages {
-            let pkg = localdb
-                .pkg(pkg_name.as_str())
-                .map_err(|_| anyhow::anyhow!("error: package '{}' was not found", pkg_name))?;
+            let pkg = alpm_ops::find_local_pkg(&handle, pkg_name)?;
             if pkg.reason() == PackageReason::Explicit {
                 rows.push(format!(
                     "{{\"name\":\"{}\",\"version\":\"{}\",\"description\":\"{}\",\"architecture\":\"{}\",\"installed_size\":{}}}",
@@ -428,9 +1158,7 @@ pub fn query_explicit_packages(global: &GlobalFlags, packages: &[String]) -> Res
     print_section_header(global, "Explicit package query", Some(&packages.join(" ")));
     let mut count = 0usize;
     for pkg_name in packages {
-        let pkg = localdb
-            .pkg(pkg_name.as_str())
-            .map_err(|_| anyhow::anyhow!("error: package '{}' was not found", pkg_name))?;
+        let pkg = alpm_ops::find_local_pkg(&handle, pkg_name)?;
         if pkg.reason() == PackageReason::Explicit {
             print_pkg_row(
                 global,
@@ -445,7 +1173,7 @@ pub fn query_explicit_packages(global: &GlobalFlags, packages: &[String]) -> Res
         }
     }
     if count == 0 {
-        print_no_results();
+        print_no_results(global);
     } else {
         print_match_count(global, count);
     }
@@ -453,16 +1181,48 @@ pub fn query_explicit_packages(global: &GlobalFlags, packages: &[String]) -> Res
     Ok(())
 }
 
-pub fn query_reverse_dependencies(global: &GlobalFlags, packages: &[String]) -> Result<()> {
+/// Prints the packages requiring `name`, then theirs, indented two spaces per
+/// level. A package reached a second time is marked instead of repeating its
+/// subtree, which also stops dependency cycles.
+fn print_reverse_tree(global: &GlobalFlags, localdb: &alpm::Db, name: &str, depth: usize, shown: &mut HashSet<String>) {
+    let Ok(pkg) = localdb.pkg(name) else {
+        return;
+    };
+    let indent = "  ".repeat(depth);
+    for dep in pkg.required_by().iter() {
+        let first_visit = shown.insert(dep.to_string());
+        if global.quiet {
+            if first_visit {
+                println!("{}", dep);
+                print_reverse_tree(global, localdb, &dep, depth + 1, shown);
+            }
+        } else if first_visit {
+            println!("{}{}", indent, dep.white().bold());
+            print_reverse_tree(global, localdb, &dep, depth + 1, shown);
+        } else {
+            println!("{}{} {}", indent, dep.white().bold(), "(already shown)".dimmed());
+        }
+    }
+}
+
+pub fn query_reverse_dependencies(global: &GlobalFlags, packages: &[String], recursive: bool) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
     let localdb = handle.localdb();
     
     for pkg_name in packages {
-        let pkg = localdb
-            .pkg(pkg_name.as_str())
-            .map_err(|_| anyhow::anyhow!("error: package '{}' was not found", pkg_name))?;
+        let pkg = alpm_ops::find_local_pkg(&handle, pkg_name)?;
         let revdeps: Vec<String> = pkg.required_by().iter().map(|name| name.to_string()).collect();
-        if revdeps.is_empty() {
+        if recursive {
+            if !global.quiet {
+                println!("{}", pkg.name().green().bold());
+            }
+            let mut shown = HashSet::from([pkg.name().to_string()]);
+            print_reverse_tree(global, localdb, pkg.name(), 1, &mut shown);
+        } else if global.quiet {
+            for dep in revdeps {
+                println!("{}", dep);
+            }
+        } else if revdeps.is_empty() {
             println!(
                 "{} {}",
                 pkg.name().green().bold(),
@@ -479,46 +1239,130 @@ pub fn query_reverse_dependencies(global: &GlobalFlags, packages: &[String]) ->
     Ok(())
 }
 
-fn normalize_query_path(path: &str) -> &str {
-    path.strip_prefix('/').unwrap_or(path)
+/// `-Q --dot`: installed packages and their dependency edges as a Graphviz
+/// digraph. Dependencies are resolved to the installed package satisfying
+/// them; with targets, only their dependency closure is included.
+pub fn print_dependency_dot(global: &GlobalFlags, targets: &[String]) -> Result<()> {
+    let handle = alpm_ops::init_handle(global)?;
+    let localdb = handle.localdb();
+    let installed = localdb.pkgs();
+    
+    let mut included: Vec<&Package> = Vec::new();
+    if targets.is_empty() {
+        included.extend(installed.iter());
+    } else {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        for target in targets {
+            let pkg = alpm_ops::find_local_pkg(&handle, target)?;
+            if seen.insert(pkg.name().to_string()) {
+                queue.push_back(pkg);
+            }
+        }
+        while let Some(pkg) = queue.pop_front() {
+            included.push(pkg);
+            for dep in pkg.depends().iter() {
+                if let Some(satisfier) = installed.find_satisfier(dep.to_string()) {
+                    if seen.insert(satisfier.name().to_string()) {
+                        queue.push_back(satisfier);
+                    }
+                }
+            }
+        }
+    }
+    
+    println!("digraph dependencies {{");
+    for pkg in &included {
+        println!("  \"{}\";", pkg.name());
+        for dep in pkg.depends().iter() {
+            if let Some(satisfier) = installed.find_satisfier(dep.to_string()) {
+                println!("  \"{}\" -> \"{}\";", pkg.name(), satisfier.name());
+            }
+        }
+    }
+    println!("}}");
+    Ok(())
+}
+
+/// Database-relative spellings of a `-Qo` argument: the path as given (made
+/// absolute, or looked up in `PATH` for a bare command name) and its
+/// symlink-resolved form. Directories get the trailing `/` libalpm stores.
+fn owner_query_paths(input: &str, root: &str) -> Vec<String> {
+    let path = if !input.contains('/') && !Path::new(input).exists() {
+        utils::find_in_path(input).unwrap_or_else(|| Path::new(input).to_path_buf())
+    } else if Path::new(input).is_absolute() {
+        Path::new(input).to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(input))
+            .unwrap_or_else(|_| Path::new(input).to_path_buf())
+    };
+    
+    let mut candidates = vec![path.clone()];
+    if let Ok(resolved) = fs::canonicalize(&path) {
+        if resolved != path {
+            candidates.push(resolved);
+        }
+    }
+    
+    let mut queries = Vec::new();
+    for candidate in candidates {
+        let is_dir = candidate.is_dir();
+        let relative = candidate.strip_prefix(root).unwrap_or(&candidate);
+        let mut query = relative.to_string_lossy().trim_start_matches('/').to_string();
+        if is_dir && !query.is_empty() && !query.ends_with('/') {
+            query.push('/');
+        }
+        if !query.is_empty() && !queries.contains(&query) {
+            queries.push(query);
+        }
+    }
+    queries
 }
 
 pub fn query_owns(global: &GlobalFlags, paths: &[String]) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
     let db = handle.localdb();
+    let mut unowned = 0usize;
     
     for input in paths {
-        let query = normalize_query_path(input);
+        let queries = owner_query_paths(input, handle.root());
         let mut found = false;
         
         for pkg in db.pkgs().iter() {
             let files = pkg.files();
-            if files.contains(query).is_some() {
-                println!(
-                    "{} {} {}",
-                    input.white().bold(),
-                    "is owned by".cyan().bold(),
-                    pkg.name().green().bold()
-                );
+            if queries.iter().any(|q| files.contains(q.as_str()).is_some()) {
+                if global.quiet {
+                    println!("{}", pkg.name());
+                } else {
+                    println!(
+                        "{} {} {}",
+                        input.white().bold(),
+                        "is owned by".cyan().bold(),
+                        pkg.name().green().bold()
+                    );
+                }
                 found = true;
-                break;
             }
         }
         
         if !found {
             eprintln!("error: {}", format!("No package owns {}", input).red());
+            unowned += 1;
         }
     }
     
+    // Unowned paths were reported above; only fail when nothing resolved
+    if unowned > 0 && unowned == paths.len() {
+        bail!("{} of {} path(s) are not owned by any package", unowned, paths.len());
+    }
     Ok(())
 }
 
 pub fn explain_why(global: &GlobalFlags, package_name: &str) -> Result<()> {
     let handle = alpm_ops::init_handle(global)?;
     let localdb = handle.localdb();
-    let target = localdb
-        .pkg(package_name)
-        .map_err(|_| anyhow::anyhow!("error: package '{}' was not found", package_name))?;
+    let target = alpm_ops::find_local_pkg(&handle, package_name)?;
 
     println!(
         "{} {}",
@@ -1,3 +1,4 @@
+use colored::Colorize;
 use std::env;
 use std::path::PathBuf;
 
@@ -5,6 +6,10 @@ pub fn is_root() -> bool {
     unsafe { libc::geteuid() == 0 }
 }
 
+pub fn stdout_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
 pub fn get_arch() -> String {
     env::consts::ARCH.to_string()
 }
@@ -22,29 +27,184 @@ pub fn arch_variants(arch: &str) -> (String, String, String) {
     }
 }
 
-pub fn check_command_exists(command: &str) -> bool {
-    let Some(path_env) = env::var_os("PATH") else {
-        return false;
+/// Parses a byte size such as `500M`, `2G`, `750K` or a plain byte count.
+pub fn parse_size(input: &str) -> Option<i64> {
+    let input = input.trim();
+    let (digits, multiplier) = match input.chars().last()?.to_ascii_uppercase() {
+        'K' => (&input[..input.len() - 1], 1024i64),
+        'M' => (&input[..input.len() - 1], 1024 * 1024),
+        'G' => (&input[..input.len() - 1], 1024 * 1024 * 1024),
+        _ => (input, 1),
+    };
+    let value = digits.trim().parse::<i64>().ok()?;
+    if value < 0 {
+        return None;
+    }
+    value.checked_mul(multiplier)
+}
+
+/// Ranks a search hit for `-Ss`/`-Qs`: 0 when the name equals a query, 1 when
+/// it starts with one, 2 when it contains one, 3 for description-only matches.
+pub fn search_relevance(name: &str, queries: &[String]) -> u8 {
+    let name = name.to_lowercase();
+    queries
+        .iter()
+        .map(|query| {
+            let query = query.to_lowercase();
+            if name == query {
+                0
+            } else if name.starts_with(&query) {
+                1
+            } else if name.contains(&query) {
+                2
+            } else {
+                3
+            }
+        })
+        .min()
+        .unwrap_or(3)
+}
+
+/// Checks a `--assume-installed` value: a package name, optionally followed by
+/// `=version`. Other comparison operators are not accepted, like pacman.
+pub fn valid_assume_installed(spec: &str) -> bool {
+    let (name, version) = match spec.split_once('=') {
+        Some((name, version)) => (name, Some(version)),
+        None => (spec, None),
     };
-    env::split_paths(&path_env).any(|dir| {
-        let candidate: PathBuf = dir.join(command);
-        candidate.is_file()
-    })
+    let name_ok = !name.is_empty()
+        && !name.starts_with(['-', '.'])
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "@._+-".contains(c));
+    let version_ok = version.is_none_or(|v| {
+        !v.is_empty() && !v.chars().any(|c| c.is_whitespace() || "<>=".contains(c))
+    });
+    name_ok && version_ok
+}
+
+pub fn check_command_exists(command: &str) -> bool {
+    find_in_path(command).is_some()
+}
+
+/// First `PATH` entry containing `command`, like `which`.
+pub fn find_in_path(command: &str) -> Option<PathBuf> {
+    let path_env = env::var_os("PATH")?;
+    env::split_paths(&path_env)
+        .map(|dir| dir.join(command))
+        .find(|candidate| candidate.is_file())
 }
 
 pub fn confirm_action(message: &str) -> bool {
+    confirm_with_default(message, true)
+}
+
+/// Asks the user to type `expected` exactly; anything else (including EOF) declines.
+pub fn confirm_typed(message: &str, expected: &str) -> bool {
+    match read_answer(message) {
+        Some(input) => input.trim() == expected,
+        None => {
+            eprintln!("{}", "no response on stdin; declining".yellow());
+            false
+        }
+    }
+}
+
+pub fn stdin_is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+/// Column count of the terminal on stdout, or None when stdout is not a terminal.
+pub fn terminal_width() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok && size.ws_col > 0 {
+        Some(size.ws_col as usize)
+    } else {
+        None
+    }
+}
+
+/// Shortens `text` to at most `max` characters, ending with an ellipsis when cut.
+pub fn truncate_with_ellipsis(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let mut out: String = text.chars().take(max - 1).collect();
+    out.push('…');
+    out
+}
+
+/// Edit distance between two strings, counted in characters.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// Up to two candidates closest to `name`, for "did you mean" hints. Names
+/// further than a third of the requested length (minimum 2) are ignored.
+pub fn closest_names<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let threshold = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+    scored.sort_unstable();
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored.into_iter().take(2).map(|(_, candidate)| candidate.to_string()).collect()
+}
+
+/// Shows `message` when stdin is a terminal and reads one line of answer.
+/// Returns None when stdin is closed or unreadable; callers must decline then.
+fn read_answer(message: &str) -> Option<String> {
     use std::io::{self, Write};
     
-    print!("{}", message);
-    io::stdout().flush().unwrap();
+    let interactive = stdin_is_tty();
+    if interactive {
+        print!("{}", message);
+        let _ = io::stdout().flush();
+    }
     
     let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    
-    let response = input.trim().to_lowercase();
-    
-    // Default to yes if empty (like pacman)
-    response.is_empty() || matches!(response.as_str(), "y" | "yes")
+    match io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => {
+            if interactive {
+                println!();
+            }
+            None
+        }
+        Ok(_) => Some(input),
+    }
+}
+
+pub fn confirm_with_default(message: &str, default_yes: bool) -> bool {
+    loop {
+        let Some(input) = read_answer(message) else {
+            // Closed or unreadable stdin: never proceed with a change unattended
+            eprintln!("{}", "no response on stdin; declining (use --noconfirm to proceed non-interactively)".yellow());
+            return false;
+        };
+        match input.trim().to_lowercase().as_str() {
+            // Empty input takes the prompt's default (like pacman)
+            "" => return default_yes,
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            other => eprintln!("{} '{}': please answer y or n", "unrecognized answer".yellow(), other),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -57,6 +217,59 @@ mod tests {
         assert!(!arch.is_empty());
     }
     
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("500M"), Some(500 * 1024 * 1024));
+        assert_eq!(parse_size("2g"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("10K"), Some(10 * 1024));
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("-1M"), None);
+    }
+    
+    #[test]
+    fn test_search_relevance() {
+        let queries = vec!["firefox".to_string()];
+        assert_eq!(search_relevance("firefox", &queries), 0);
+        assert_eq!(search_relevance("Firefox-i18n-de", &queries), 1);
+        assert_eq!(search_relevance("torbrowser-firefox", &queries), 2);
+        assert_eq!(search_relevance("librewolf", &queries), 3);
+        let several = vec!["web".to_string(), "librewolf".to_string()];
+        assert_eq!(search_relevance("librewolf", &several), 0);
+    }
+
+    #[test]
+    fn test_valid_assume_installed() {
+        assert!(valid_assume_installed("bar"));
+        assert!(valid_assume_installed("bar=1.0"));
+        assert!(valid_assume_installed("lib32-glibc=1:2.40-1"));
+        assert!(!valid_assume_installed(""));
+        assert!(!valid_assume_installed("bar="));
+        assert!(!valid_assume_installed("=1.0"));
+        assert!(!valid_assume_installed("bar>=1.0"));
+        assert!(!valid_assume_installed("bar=1.0=2"));
+        assert!(!valid_assume_installed("-bar"));
+    }
+
+    #[test]
+    fn test_truncate_with_ellipsis() {
+        assert_eq!(truncate_with_ellipsis("short", 10), "short");
+        assert_eq!(truncate_with_ellipsis("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate_with_ellipsis("a longer description", 8), "a longe…");
+        assert_eq!(truncate_with_ellipsis("abc", 0), "");
+    }
+    
+    #[test]
+    fn test_closest_names() {
+        assert_eq!(levenshtein("firefox", "firefox"), 0);
+        assert_eq!(levenshtein("fierfox", "firefox"), 2);
+        assert_eq!(levenshtein("", "abc"), 3);
+        let names = ["firefox", "firefox-esr", "thunderbird", "vim"];
+        assert_eq!(closest_names("fierfox", names), vec!["firefox"]);
+        assert_eq!(closest_names("vin", names), vec!["vim"]);
+        assert!(closest_names("chromium", names).is_empty());
+    }
+    
     #[test]
     fn test_check_command_exists() {
         assert!(check_command_exists("ls"));